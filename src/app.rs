@@ -2,15 +2,19 @@ use dirs::{config_local_dir, document_dir};
 
 use std::{
     any::Any,
-    fs::{copy, create_dir_all, read_dir, remove_dir_all},
+    collections::BTreeMap,
+    fs::{create_dir_all, read_dir, remove_dir_all},
     io::{Seek, SeekFrom},
     path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
+#[cfg(test)]
+use chrono::TimeZone;
 use chrono::{
     prelude::{DateTime, Local},
-    TimeZone,
+    TimeDelta,
 };
 use registry::{Hive, Security};
 use serde::{Deserialize, Serialize};
@@ -21,8 +25,24 @@ use serde_json::{
 
 use thiserror::Error;
 
+use crate::{
+    copy::{default_threads, measure, Copier, CopyProgress, Crypt, Operation, ReflinkMode},
+    crypto::{is_encrypted, CryptoError, SnapshotKey, KEY_FILE},
+    filter::{Preview, TargetFilter},
+    history::{History, HistoryEntry, HistoryKind, HISTORY_FILE},
+    hooks::{run_hook, HookError, Hooks},
+    rcon::{RconConfig, SavePause},
+    s3::{S3Client, S3Config, S3Error},
+    schedule::Schedule,
+    sftp::{SftpClient, SftpConfig, SftpError},
+    store::{parse_snapshot_name, snapshot_name, BackupStore, LocalStore},
+    webhook::{notify_all, Notification, Webhook},
+};
+
 // region: Constants
 
+const INSTANCE: &str = r"Instances\BigChadGuys Plus (w Cobblemon)";
+
 const TO_COPY: [&str; 5] = [
     r"Instances\BigChadGuys Plus (w Cobblemon)\options.txt",
     r"Instances\BigChadGuys Plus (w Cobblemon)\saves",
@@ -33,35 +53,67 @@ const TO_COPY: [&str; 5] = [
 
 pub const TITLE: &str = " Crucible ";
 
-pub const TIPS_MAIN: [(&str, &str); 5] = [
+/// Name of the per-snapshot report written next to the copied targets.
+pub const REPORT_FILE: &str = "crucible-report.json";
+
+pub const TIPS_MAIN: &[(&str, &str)] = &[
     ("q", "uit"),
     ("m", "anually back up"),
     ("s", "ettings"),
     ("b", "ackups"),
-    ("", ""),
+    ("h", "istory"),
+    ("p", "ause or resume"),
+    ("c", "ancel backup or restore"),
 ];
-pub const TIPS_SETTINGS: [(&str, &str); 5] = [
+pub const TIPS_SETTINGS: &[(&str, &str)] = &[
     ("q", "uit"),
     ("m", "ax backups"),
     ("t", "argets"),
     ("f", "requency"),
     ("p", "ath"),
+    ("g", "ame-aware"),
+    ("c", "onsistency"),
+    ("s", "chedule"),
+    ("w", "atch"),
+    ("e", "ncryption"),
+    ("r", "eflinks"),
 ];
-pub const TIPS_BACKUPS: [(&str, &str); 5] = [
+pub const TIPS_BACKUPS: &[(&str, &str)] = &[
     ("q", "uit"),
     ("r", "estore"),
     ("d", "elete"),
-    ("", ""),
+    ("v", "erify"),
     ("", ""),
 ];
-pub const TIPS_TARGETS: [(&str, &str); 5] = [
+pub const TIPS_TARGETS: &[(&str, &str)] = &[
     ("q", "uit"),
     ("a", "dd"),
     ("d", "elete"),
     ("e", "dit"),
+    ("v", "iew matches"),
+];
+/// Delay before the first retry of a failed backup; doubles with every further failure.
+pub const RETRY_BASE: Duration = Duration::from_secs(30);
+pub const RETRY_MAX: Duration = Duration::from_secs(60 * 15);
+/// Consecutive failures after which the status area turns into a persistent alert.
+pub const ESCALATE_AFTER: u32 = 3;
+
+/// How long a world's region files and `level.dat` must go untouched before it is copied.
+pub const SETTLE_WINDOW: Duration = Duration::from_secs(5);
+pub const SETTLE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const SETTLE_POLL: Duration = Duration::from_secs(1);
+/// Copies of a world attempted before it is flagged as possibly inconsistent.
+pub const WORLD_COPY_ATTEMPTS: u32 = 3;
+
+pub const TIPS_HISTORY: &[(&str, &str)] = &[
+    ("q", "uit"),
+    ("f", "ilter"),
+    ("↑ or w", " Scroll up"),
+    ("↓ or s", " Scroll down"),
     ("", ""),
 ];
 pub const TIPS_CONFIRM: [(&str, &str); 3] = [("y", "es"), ("n", "o"), ("q", "uit")];
+pub const TIPS_QUIT: [(&str, &str); 3] = [("w", "ait"), ("c", "ancel it"), ("n", "ot yet")];
 // pub const TIPS_EDIT: [(&str, &str); 5] = [
 //     ("ESC", ""),
 //     ("ENTER", ""),
@@ -69,31 +121,83 @@ pub const TIPS_CONFIRM: [(&str, &str); 3] = [("y", "es"), ("n", "o"), ("q", "uit
 //     ("", ""),
 //     ("", ""),
 // ];
-pub const TIPS_PATH: [(&str, &str); 5] = [
+pub const TIPS_PATH: &[(&str, &str)] = &[
     ("q", "uit"),
     ("ENTER", " Go into"),
     ("↑ or s", " Move up"),
     ("↓ or w", " Move down"),
     ("t", "arget"),
 ];
-pub const TIPS_NUM: [(&str, &str); 5] = [
+pub const TIPS_NUM: &[(&str, &str)] = &[
     ("q", "uit"),
     ("ENTER", " Confirm"),
     ("← or →", " Move"),
     ("0123456789", ""),
     ("", ""),
 ];
+pub const TIPS_TEXT: &[(&str, &str)] = &[
+    ("ESC", " Cancel"),
+    ("ENTER", " Confirm, empty to clear"),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+];
+pub const TIPS_PASSPHRASE: &[(&str, &str)] = &[
+    ("ESC", " Skip"),
+    ("ENTER", " Unlock"),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+];
 
 // endregion: Constants
 
 // region: Core classes
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Configuration {
     pub path: PathBuf,
     pub frequency: Duration,
+    /// Calendar rule for automatic backups; `frequency` is used when unset.
+    pub schedule: Option<Schedule>,
+    /// Back up when the targets change instead of on a timer.
+    pub watch: bool,
+    /// How long the targets must go unchanged in watch mode before a backup starts.
+    pub quiet_period: Duration,
     pub targets: Vec<String>,
     pub max_backups: u8,
+    /// Instance folder, relative to the Minecraft root, that the game is launched from.
+    pub instance: String,
+    /// Only run scheduled backups while the instance is running, plus one right after it exits.
+    pub game_aware: bool,
+    /// Wait for worlds to stop changing before copying them, and recopy any changed mid-copy.
+    pub consistent_worlds: bool,
+    /// Server to hold world saving on while a backup runs.
+    pub rcon: Option<RconConfig>,
+    /// Automatic backups are suspended while this is active. Manual backups still run.
+    pub pause: Option<Pause>,
+    pub hooks: Hooks,
+    pub webhooks: Vec<Webhook>,
+    /// Secondary destinations every finished snapshot is copied to.
+    pub mirrors: Vec<Mirror>,
+    /// Bucket every finished snapshot is uploaded to.
+    pub s3: Option<S3Config>,
+    /// Folder on an SSH server every finished snapshot is uploaded to.
+    pub sftp: Option<SftpConfig>,
+    /// Whether new snapshots are encrypted with the passphrase.
+    pub encrypt: bool,
+    /// Entered at startup or taken from `CRUCIBLE_PASSPHRASE`; never written to the config file.
+    #[serde(skip)]
+    pub passphrase: Option<String>,
+    /// Files copied at once; 0 uses one thread per CPU core.
+    pub copy_threads: usize,
+    /// Whether files are cloned copy-on-write where the filesystem supports it.
+    pub reflink: ReflinkMode,
+    /// Gitignore-style patterns per target for files to leave out; `!` brings a file back.
+    pub target_patterns: BTreeMap<String, Vec<String>>,
+    /// Patterns per instance, applied to every target before the target's own.
+    pub profile_patterns: BTreeMap<String, Vec<String>>,
 }
 
 impl Default for Configuration {
@@ -104,8 +208,27 @@ impl Default for Configuration {
                 None => PathBuf::from("./"),
             },
             frequency: Duration::from_secs(60 * 15),
+            schedule: None,
+            watch: false,
+            quiet_period: Duration::from_secs(60 * 2),
             targets: TO_COPY.map(|target| target.to_string()).to_vec(),
             max_backups: 10,
+            instance: INSTANCE.to_string(),
+            game_aware: false,
+            consistent_worlds: false,
+            rcon: None,
+            pause: None,
+            hooks: Hooks::default(),
+            webhooks: Vec::new(),
+            mirrors: Vec::new(),
+            s3: None,
+            sftp: None,
+            encrypt: false,
+            passphrase: None,
+            copy_threads: 0,
+            reflink: ReflinkMode::Auto,
+            target_patterns: BTreeMap::new(),
+            profile_patterns: BTreeMap::new(),
         }
     }
 }
@@ -123,16 +246,302 @@ impl std::fmt::Display for Configuration {
 }
 
 impl Configuration {
+    pub fn remotes(&self) -> Vec<Remote> {
+        self.s3
+            .iter()
+            .cloned()
+            .map(Remote::S3)
+            .chain(self.sftp.iter().cloned().map(Remote::Sftp))
+            .collect()
+    }
+
+    /// A copier with these settings that reports to `progress`.
+    pub fn copier(&self, progress: &Arc<CopyProgress>) -> Copier {
+        Copier::new(self.copy_threads, self.reflink, Arc::clone(progress))
+    }
+
+    /// The selected instance's patterns followed by those of `target`.
+    pub fn patterns_for(&self, target: &str) -> Vec<String> {
+        self.profile_patterns
+            .get(&self.instance)
+            .into_iter()
+            .chain(self.target_patterns.get(target))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// The filter for `target`, found at `path`, or `None` if it has no patterns.
+    pub fn filter_for(&self, target: &str, path: &Path) -> BackupResult<Option<TargetFilter>> {
+        let patterns = self.patterns_for(target);
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+        TargetFilter::new(target, path, &patterns).map(Some)
+    }
+
     pub fn to_ui_list(&self) -> Vec<(&str, String)> {
         vec![
             ("Path", String::from(self.path.to_str().unwrap())),
             ("Frequency", duration_to_readable(self.frequency)),
+            (
+                "Schedule",
+                match &self.schedule {
+                    Some(schedule) => schedule.to_string(),
+                    None => String::from("off"),
+                },
+            ),
+            (
+                "Watch",
+                match self.watch {
+                    true => format!("on, {} quiet", duration_to_readable(self.quiet_period)),
+                    false => String::from("off"),
+                },
+            ),
             ("Max backups", self.max_backups.to_string()),
             ("Target count", self.targets.len().to_string()),
+            ("Mirrors", self.mirrors.len().to_string()),
+            (
+                "S3",
+                match &self.s3 {
+                    Some(s3) => s3.display(),
+                    None => String::from("off"),
+                },
+            ),
+            (
+                "SFTP",
+                match &self.sftp {
+                    Some(sftp) => sftp.display(),
+                    None => String::from("off"),
+                },
+            ),
+            (
+                "Encryption",
+                match (self.encrypt, &self.passphrase) {
+                    (false, _) => String::from("off"),
+                    (true, Some(_)) => String::from("on"),
+                    (true, None) => String::from("on, no passphrase entered"),
+                },
+            ),
+            ("Instance", self.instance.clone()),
+            (
+                "RCON",
+                match &self.rcon {
+                    Some(rcon) => rcon.address.clone(),
+                    None => String::from("off"),
+                },
+            ),
+            (
+                "Game-aware",
+                String::from(match self.game_aware {
+                    true => "on",
+                    false => "off",
+                }),
+            ),
+            (
+                "Consistency",
+                String::from(match self.consistent_worlds {
+                    true => "wait for saves to settle",
+                    false => "off",
+                }),
+            ),
+            (
+                "Copy threads",
+                match self.copy_threads {
+                    0 => format!("auto ({})", default_threads()),
+                    threads => threads.to_string(),
+                },
+            ),
+            ("Reflinks", String::from(self.reflink.label())),
         ]
     }
 }
 
+/// Number of files and bytes moved by a copy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct CopyStats {
+    pub files: u64,
+    pub bytes: u64,
+    /// Files among `files` that were cloned copy-on-write rather than copied.
+    #[serde(default)]
+    pub reflinked: u64,
+}
+
+impl std::ops::AddAssign for CopyStats {
+    fn add_assign(&mut self, other: CopyStats) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.reflinked += other.reflinked;
+    }
+}
+
+/// A second place to keep snapshots, e.g. another drive or a mounted NAS share.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Mirror {
+    pub path: PathBuf,
+    pub max_backups: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MirrorOutcome {
+    pub path: PathBuf,
+    /// Why the copy or its verification failed; `None` if the mirror holds a verified copy.
+    pub error: Option<String>,
+    #[serde(default)]
+    pub pruned: Vec<PathBuf>,
+}
+
+/// Where a snapshot listed on the Backups screen is kept.
+#[derive(Clone)]
+pub enum Location {
+    /// The backup folder, `Configuration::path`.
+    Local,
+    Remote(Remote),
+}
+
+impl Location {
+    /// Opens the store holding the location's snapshots.
+    pub fn open(&self, config: &Configuration) -> BackupResult<Box<dyn BackupStore>> {
+        match self {
+            Location::Local => Ok(Box::new(LocalStore::new(&config.path))),
+            Location::Remote(remote) => remote.open(),
+        }
+    }
+
+    pub fn display(&self, config: &Configuration) -> String {
+        match self {
+            Location::Local => config.path.display().to_string(),
+            Location::Remote(remote) => remote.display(),
+        }
+    }
+}
+
+/// What the patterns of a target matched, for the Targets screen.
+pub struct TargetPreview {
+    pub target: String,
+    /// The patterns it was worked out with; it no longer applies once they change.
+    pub patterns: Vec<String>,
+    /// `None` while the target is still being walked.
+    pub result: Option<Result<Preview, String>>,
+}
+
+/// How the last restore run by the worker went.
+#[derive(Clone, PartialEq)]
+pub enum RestoreStatus {
+    Succeeded(DateTime<Local>, CopyStats),
+    Failed(DateTime<Local>, String),
+}
+
+/// A destination on another machine that finished snapshots are uploaded to.
+#[derive(Clone)]
+pub enum Remote {
+    S3(S3Config),
+    Sftp(SftpConfig),
+}
+
+impl Remote {
+    /// Tag shown next to the remote's snapshots in the Backups screen.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Remote::S3(_) => "s3",
+            Remote::Sftp(_) => "sftp",
+        }
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            Remote::S3(config) => config.display(),
+            Remote::Sftp(config) => config.display(),
+        }
+    }
+
+    fn max_backups(&self) -> u8 {
+        match self {
+            Remote::S3(config) => config.max_backups,
+            Remote::Sftp(config) => config.max_backups,
+        }
+    }
+
+    /// Connects to the remote's store.
+    pub fn open(&self) -> BackupResult<Box<dyn BackupStore>> {
+        match self {
+            Remote::S3(config) => Ok(Box::new(S3Client::new(config.clone()))),
+            Remote::Sftp(config) => Ok(Box::new(SftpClient::connect(config.clone())?)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TargetFailure {
+    pub target: String,
+    pub error: String,
+}
+
+/// Outcome of a single backup, target by target.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct BackupReport {
+    pub copied: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<TargetFailure>,
+    #[serde(default)]
+    pub stats: CopyStats,
+    /// Older snapshots removed by retention after this one was written.
+    #[serde(default)]
+    pub pruned: Vec<PathBuf>,
+    /// Worlds that kept changing while being copied and may be torn.
+    #[serde(default)]
+    pub inconsistent: Vec<PathBuf>,
+    /// Problems around the copy itself, such as failing to pause server saves.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Replication to each configured mirror. Failures here don't fail the backup.
+    #[serde(default)]
+    pub mirrors: Vec<MirrorOutcome>,
+}
+
+impl BackupReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} copied, {} skipped, {} failed ({} files, {}{})",
+            self.copied.len(),
+            self.skipped.len(),
+            self.failed.len(),
+            self.stats.files,
+            bytes_to_readable(self.stats.bytes),
+            match self.stats.reflinked {
+                0 => String::new(),
+                reflinked => format!(", {} reflinked", reflinked),
+            },
+        )
+    }
+
+    pub fn to_ui_lines(&self) -> Vec<String> {
+        let mut lines = vec![self.summary()];
+        lines.extend(
+            self.skipped
+                .iter()
+                .map(|t| format!("skipped (missing): {}", t)),
+        );
+        lines.extend(
+            self.failed
+                .iter()
+                .map(|f| format!("failed: {} ({})", f.target, f.error)),
+        );
+        lines.extend(
+            self.inconsistent
+                .iter()
+                .map(|w| format!("possibly inconsistent: {}", w.display())),
+        );
+        lines.extend(self.warnings.iter().map(|w| format!("warning: {}", w)));
+        lines.extend(self.mirrors.iter().filter_map(|m| {
+            m.error
+                .as_ref()
+                .map(|e| format!("mirror failed: {} ({})", m.path.display(), e))
+        }));
+        lines
+    }
+}
+
 // endregion: Core classes
 
 // region: Custom enums
@@ -145,7 +554,11 @@ pub enum CurrentScreen {
     Target,
     Targets,
     Frequency,
+    Schedule,
+    Pause,
+    Passphrase,
     Max,
+    History,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -155,23 +568,454 @@ pub enum Action {
     Edit,
     ConfirmRestore,
     ConfirmNonExistent,
+    ConfirmQuit,
     None,
 }
 
+#[derive(Clone, PartialEq)]
+pub enum BackupStatus {
+    Idle,
+    Succeeded(DateTime<Local>, BackupReport),
+    Failed {
+        at: DateTime<Local>,
+        error: String,
+        attempts: u32,
+        retry_at: DateTime<Local>,
+    },
+}
+
+impl BackupStatus {
+    pub fn is_alert(&self) -> bool {
+        match self {
+            BackupStatus::Failed { attempts, .. } => *attempts >= ESCALATE_AFTER,
+            _ => false,
+        }
+    }
+}
+
 // endregion: Custom enums
 
+// region: Clock
+
+/// Source of the current time for scheduling and snapshot naming, so tests can drive it by hand.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+#[cfg(test)]
+pub struct FakeClock(std::sync::Mutex<DateTime<Local>>);
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(start: DateTime<Local>) -> FakeClock {
+        FakeClock(std::sync::Mutex::new(start))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.0.lock().unwrap();
+        *now += TimeDelta::from_std(by).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.0.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Exponential backoff for the `attempt`-th consecutive failure, capped at `RETRY_MAX`.
+pub fn retry_backoff(attempt: u32) -> Duration {
+    RETRY_BASE
+        .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .unwrap_or(RETRY_MAX)
+        .min(RETRY_MAX)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Pause {
+    Indefinitely,
+    Until(DateTime<Local>),
+}
+
+impl Pause {
+    pub fn is_active(&self, now: DateTime<Local>) -> bool {
+        match self {
+            Pause::Indefinitely => true,
+            Pause::Until(until) => now < *until,
+        }
+    }
+
+    /// Time left before the pause lifts by itself, or `None` if only the user can lift it.
+    pub fn remaining(&self, now: DateTime<Local>) -> Option<Duration> {
+        match self {
+            Pause::Indefinitely => None,
+            Pause::Until(until) => Some(
+                until
+                    .signed_duration_since(now)
+                    .to_std()
+                    .unwrap_or(Duration::from_secs(0)),
+            ),
+        }
+    }
+}
+
+#[test]
+pub fn test_pause() {
+    let now = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    let until = Pause::Until(now + TimeDelta::hours(2));
+    assert!(until.is_active(now));
+    assert_eq!(until.remaining(now), Some(Duration::from_secs(60 * 60 * 2)));
+    assert!(!until.is_active(now + TimeDelta::hours(2)));
+    assert_eq!(
+        until.remaining(now + TimeDelta::hours(3)),
+        Some(Duration::from_secs(0))
+    );
+    assert!(Pause::Indefinitely.is_active(now));
+    assert_eq!(Pause::Indefinitely.remaining(now), None);
+
+    // The pause is part of the saved configuration so it outlives a restart.
+    let config = Configuration {
+        pause: Some(until),
+        ..Configuration::default()
+    };
+    let saved: Configuration =
+        serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(saved.pause, Some(until));
+}
+
+/// Keeps track of when the last scheduled backup ran and when the next one is due.
+pub struct Scheduler {
+    last_run: DateTime<Local>,
+    failures: u32,
+    retry_at: Option<DateTime<Local>>,
+    /// Most recent change to the targets not yet covered by a backup, in watch mode.
+    changed_at: Option<DateTime<Local>>,
+}
+
+impl Scheduler {
+    pub fn new(clock: &dyn Clock) -> Scheduler {
+        Scheduler {
+            last_run: clock.now(),
+            failures: 0,
+            retry_at: None,
+            changed_at: None,
+        }
+    }
+
+    /// When the next backup is due, or `None` while watch mode has no changes to back up.
+    pub fn next_run(&self, config: &Configuration) -> Option<DateTime<Local>> {
+        let every =
+            || self.last_run + TimeDelta::from_std(config.frequency).unwrap_or(TimeDelta::zero());
+        // A schedule that never fires again (e.g. a cron rule pinned to a past year) falls back to the frequency.
+        let scheduled = match (config.watch, &config.schedule) {
+            (true, _) => self.changed_at.map(|changed| {
+                changed + TimeDelta::from_std(config.quiet_period).unwrap_or(TimeDelta::zero())
+            }),
+            (false, Some(schedule)) => {
+                Some(schedule.next_after(self.last_run).unwrap_or_else(every))
+            }
+            (false, None) => Some(every()),
+        };
+        match (self.retry_at, scheduled) {
+            (Some(retry), Some(scheduled)) => Some(retry.min(scheduled)),
+            (retry, scheduled) => retry.or(scheduled),
+        }
+    }
+
+    /// Never true while automatic backups are paused, so a backup that falls due during a pause
+    /// is still due once it lifts.
+    pub fn is_due(&self, clock: &dyn Clock, config: &Configuration) -> bool {
+        if config
+            .pause
+            .is_some_and(|pause| pause.is_active(clock.now()))
+        {
+            return false;
+        }
+        match self.next_run(config) {
+            Some(next) => clock.now() >= next,
+            None => false,
+        }
+    }
+
+    pub fn mark_run(&mut self, at: DateTime<Local>) {
+        self.last_run = at;
+        self.changed_at = None;
+    }
+
+    /// Notes a write to the targets; in watch mode this pushes the next backup back by the quiet period.
+    pub fn record_change(&mut self, at: DateTime<Local>) {
+        self.changed_at = Some(at);
+    }
+
+    /// Schedules a retry after a failed backup and returns the number of consecutive failures.
+    pub fn record_failure(&mut self, at: DateTime<Local>) -> u32 {
+        self.failures += 1;
+        self.retry_at = Some(
+            at + TimeDelta::from_std(retry_backoff(self.failures)).unwrap_or(TimeDelta::zero()),
+        );
+        self.failures
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+}
+
+#[test]
+pub fn test_scheduler_next_run() {
+    let config = Configuration {
+        frequency: Duration::from_secs(60 * 15),
+        ..Configuration::default()
+    };
+    // No clock change falls near this, so the result is the same in any local time zone.
+    let start = Local.with_ymd_and_hms(2024, 6, 1, 11, 50, 0).unwrap();
+    let clock = FakeClock::new(start);
+    let mut scheduler = Scheduler::new(&clock);
+
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(start + TimeDelta::minutes(15))
+    );
+    clock.advance(Duration::from_secs(60 * 15 - 1));
+    assert!(!scheduler.is_due(&clock, &config));
+    clock.advance(Duration::from_secs(1));
+    assert!(scheduler.is_due(&clock, &config));
+
+    scheduler.mark_run(clock.now());
+    assert!(!scheduler.is_due(&clock, &config));
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(start + TimeDelta::minutes(30))
+    );
+
+    // A frequency change takes effect relative to the last run, not the moment of the change.
+    let config = Configuration {
+        frequency: Duration::from_secs(60 * 5),
+        ..config
+    };
+    clock.advance(Duration::from_secs(60 * 6));
+    assert!(scheduler.is_due(&clock, &config));
+}
+
+#[test]
+pub fn test_scheduler_pause() {
+    let start = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    let config = Configuration {
+        frequency: Duration::from_secs(60 * 15),
+        pause: Some(Pause::Until(start + TimeDelta::hours(1))),
+        ..Configuration::default()
+    };
+    let clock = FakeClock::new(start);
+    let mut scheduler = Scheduler::new(&clock);
+
+    // The backup falls due half way through the pause but waits for it to end.
+    clock.advance(Duration::from_secs(60 * 30));
+    assert!(!scheduler.is_due(&clock, &config));
+    clock.advance(Duration::from_secs(60 * 30));
+    assert!(scheduler.is_due(&clock, &config));
+    scheduler.mark_run(clock.now());
+    assert!(!scheduler.is_due(&clock, &config));
+
+    let config = Configuration {
+        pause: Some(Pause::Indefinitely),
+        ..config
+    };
+    clock.advance(Duration::from_secs(60 * 60));
+    assert!(!scheduler.is_due(&clock, &config));
+    let config = Configuration {
+        pause: None,
+        ..config
+    };
+    assert!(scheduler.is_due(&clock, &config));
+}
+
+#[test]
+pub fn test_scheduler_calendar() {
+    let config = Configuration {
+        schedule: Some("every 10m 18:00-02:00".parse().unwrap()),
+        ..Configuration::default()
+    };
+    let start = Local.with_ymd_and_hms(2024, 6, 1, 17, 0, 0).unwrap();
+    let clock = FakeClock::new(start);
+    let mut scheduler = Scheduler::new(&clock);
+
+    // The frequency no longer applies; nothing runs until the window opens.
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(Local.with_ymd_and_hms(2024, 6, 1, 18, 0, 0).unwrap())
+    );
+    clock.advance(Duration::from_secs(60 * 60));
+    assert!(scheduler.is_due(&clock, &config));
+    scheduler.mark_run(clock.now());
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(Local.with_ymd_and_hms(2024, 6, 1, 18, 10, 0).unwrap())
+    );
+
+    // Retries still take precedence over a distant calendar slot.
+    let failed_at = Local.with_ymd_and_hms(2024, 6, 2, 2, 0, 0).unwrap();
+    scheduler.mark_run(failed_at);
+    scheduler.record_failure(failed_at);
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(failed_at + TimeDelta::seconds(30))
+    );
+}
+
+#[test]
+pub fn test_scheduler_watch() {
+    let config = Configuration {
+        watch: true,
+        quiet_period: Duration::from_secs(60 * 2),
+        ..Configuration::default()
+    };
+    let start = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+    let clock = FakeClock::new(start);
+    let mut scheduler = Scheduler::new(&clock);
+    assert_eq!(scheduler.next_run(&config), None);
+    clock.advance(Duration::from_secs(60 * 60));
+    assert!(!scheduler.is_due(&clock, &config));
+
+    // Every write restarts the quiet period.
+    scheduler.record_change(clock.now());
+    clock.advance(Duration::from_secs(90));
+    scheduler.record_change(clock.now());
+    clock.advance(Duration::from_secs(90));
+    assert!(!scheduler.is_due(&clock, &config));
+    clock.advance(Duration::from_secs(30));
+    assert!(scheduler.is_due(&clock, &config));
+
+    scheduler.mark_run(clock.now());
+    assert_eq!(scheduler.next_run(&config), None);
+}
+
+#[test]
+pub fn test_scheduler_retry_backoff() {
+    let config = Configuration {
+        frequency: Duration::from_secs(60 * 60),
+        ..Configuration::default()
+    };
+    let start = Local.with_ymd_and_hms(2024, 11, 3, 0, 0, 0).unwrap();
+    let clock = FakeClock::new(start);
+    let mut scheduler = Scheduler::new(&clock);
+
+    assert_eq!(scheduler.record_failure(clock.now()), 1);
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(start + TimeDelta::seconds(30))
+    );
+    clock.advance(Duration::from_secs(30));
+    assert!(scheduler.is_due(&clock, &config));
+
+    scheduler.mark_run(clock.now());
+    assert_eq!(scheduler.record_failure(clock.now()), 2);
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(clock.now() + TimeDelta::seconds(60))
+    );
+
+    assert_eq!(retry_backoff(3), Duration::from_secs(120));
+    assert_eq!(retry_backoff(10), RETRY_MAX);
+    assert_eq!(retry_backoff(u32::MAX), RETRY_MAX);
+
+    scheduler.record_success();
+    assert_eq!(
+        scheduler.next_run(&config),
+        Some(clock.now() + TimeDelta::hours(1))
+    );
+}
+
+// endregion: Clock
+
 // region: Error types
 
 #[derive(Error, Debug)]
 pub enum BackupError {
-    #[error("error opening file '`{0}`'")]
-    FileError(#[from] std::io::Error),
-    // #[error("unable to create directory '`{0}`'")]
-    // TargetFolderError(String),
-    // #[error("std::io::error -> '`{0}`'")]
-    // CopyFileError(std::io::Error),
-    #[error("unable to remove directory '`{0}`'")]
-    RemoveFolderError(std::io::Error),
+    #[error("unable to copy '{}' to '{}': {source}", from.display(), to.display())]
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("cancelled")]
+    Cancelled,
+    #[error("unable to reflink '{}' to '{}': {source}", from.display(), to.display())]
+    Reflink {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("unable to move '{}' to '{}': {source}", from.display(), to.display())]
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid pattern for '{target}': {source}")]
+    Pattern {
+        target: String,
+        source: ignore::Error,
+    },
+    #[error("unable to create directory '{}': {source}", path.display())]
+    CreateDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("unable to read directory '{}': {source}", path.display())]
+    ReadDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("unable to remove directory '{}': {source}", path.display())]
+    RemoveDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("unable to write file '{}': {source}", path.display())]
+    WriteFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("nothing was backed up ({0} targets missing, {1} failed)")]
+    NothingCopied(usize, usize),
+    #[error("cancelled by pre-hook: {0}")]
+    Vetoed(HookError),
+    #[error("unable to read '{}': {source}", path.display())]
+    ReadFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("'{}' does not match the original", path.display())]
+    Mismatch { path: PathBuf },
+    #[error("no snapshot named '{0}'")]
+    MissingSnapshot(String),
+    #[error("mirror folder '{}' does not exist; is it mounted?", .0.display())]
+    MissingMirror(PathBuf),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    S3(#[from] S3Error),
+    #[error(transparent)]
+    Sftp(#[from] SftpError),
 }
 
 #[derive(Error, Debug)]
@@ -182,12 +1026,28 @@ pub enum GeneralError {
     FileError(#[from] std::io::Error),
     #[error("`{0}`")]
     Error(String),
+    #[error("{0}")]
+    Backup(#[from] BackupError),
     #[error("Error joining worker thread after non-erroneous drawing loop: `{0:?}`")]
     JustBackupWorker(Box<dyn Any + Send>),
     #[error("Error joining worker thread after erroneous drawing loop: `{0:?}`\n\n`{1}`")]
     LoopAndBackupWorker(Box<dyn Any + Send>, String),
 }
 
+impl BackupError {
+    /// Whether a restore that failed with this error stopped before writing to the game directory.
+    pub fn left_game_untouched(&self) -> bool {
+        matches!(
+            self,
+            BackupError::Vetoed(_)
+                | BackupError::Crypto(_)
+                | BackupError::MissingSnapshot(_)
+                | BackupError::S3(_)
+                | BackupError::Sftp(_)
+        )
+    }
+}
+
 pub type BackupResult<T> = std::result::Result<T, BackupError>;
 pub type CodeResult<T> = std::result::Result<T, GeneralError>;
 
@@ -214,6 +1074,28 @@ pub fn duration_to_readable(duration: Duration) -> String {
     segments.join(", ")
 }
 
+pub fn bytes_to_readable(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+#[test]
+pub fn test_bytes_to_readable() {
+    assert_eq!(bytes_to_readable(0), "0 B");
+    assert_eq!(bytes_to_readable(1023), "1023 B");
+    assert_eq!(bytes_to_readable(1536), "1.5 KiB");
+    assert_eq!(bytes_to_readable(5 * 1024 * 1024 * 1024), "5.0 GiB");
+}
+
 pub fn retrieve_minecraft_path() -> CodeResult<PathBuf> {
     match Hive::CurrentUser.open(r"Software\Overwolf\CurseForge", Security::Read) {
         Ok(regkey) => match regkey.value("minecraft_root") {
@@ -332,6 +1214,7 @@ pub fn test_write_config() {
         frequency: Duration::from_secs(60 * 15),
         targets: TO_COPY.map(|entry| entry.to_string()).to_vec(),
         max_backups: 10,
+        ..Configuration::default()
     };
 
     let filepath = match get_config_path() {
@@ -363,123 +1246,331 @@ pub fn test_write_config() {
     }
 }
 
-/// Safely compare two `std::time::Duration` objects, returning a default value
-/// (or 0.0 if provided default is negative).
-pub fn duration_compare(left: Duration, right: Duration, on_error: Option<f64>) -> Duration {
-    std::panic::set_hook(Box::new(|_info| {}));
-    let result = match std::panic::catch_unwind(|| left - right) {
-        Ok(s) => s,
-        Err(_) => Duration::from_secs_f64(match on_error {
-            Some(val) => {
-                if val < 0.0 {
-                    0.0
-                } else {
-                    val
-                }
-            }
-            None => 0.0,
-        }),
+pub fn make_dir(path: &Path) -> BackupResult<()> {
+    create_dir_all(path).map_err(|source| BackupError::CreateDir {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub fn list_dir(path: &Path) -> BackupResult<Vec<std::fs::DirEntry>> {
+    let read_error = |source| BackupError::ReadDir {
+        path: path.to_path_buf(),
+        source,
     };
-    let _ = std::panic::take_hook();
-    result
+    read_dir(path)
+        .map_err(read_error)?
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(read_error)
 }
 
-#[test]
-pub fn test_duration_compare() {
-    assert_eq!(
-        duration_compare(Duration::from_secs(4), Duration::from_secs(2), None).as_secs(),
-        2
-    );
-    assert_eq!(
-        duration_compare(Duration::from_secs(2), Duration::from_secs(4), Some(0.0)).as_secs(),
-        0
-    );
-    assert_eq!(
-        duration_compare(Duration::from_secs(2), Duration::from_secs(4), Some(-1.0)).as_secs(),
-        0
-    );
-    assert_eq!(
-        duration_compare(Duration::from_secs(2), Duration::from_secs(4), Some(2.1)).as_secs(),
-        2
-    );
+pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> BackupResult<CopyStats> {
+    Copier::default().copy_dir(src.as_ref(), dst.as_ref())
 }
 
-pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
-    create_dir_all(&dst)?;
-    if src.as_ref().is_file() {
-        copy(
-            &src,
-            dst.as_ref().join(match src.as_ref().file_name() {
-                Some(v) => v,
-                None => std::ffi::OsStr::new("unknown"),
-            }),
-        )?;
-    } else {
-        for entry in read_dir(src)? {
-            let entry = entry?;
-            let ty = entry.file_type()?;
-            if ty.is_dir() {
-                copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
-            } else {
-                copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
-            }
+fn files_equal(a: &Path, b: &Path) -> BackupResult<bool> {
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(|file| std::io::BufReader::with_capacity(1 << 16, file))
+            .map_err(|source| BackupError::ReadFile {
+                path: path.to_path_buf(),
+                source,
+            })
+    };
+    let fill = |reader: &mut std::io::BufReader<std::fs::File>, path: &Path| {
+        std::io::BufRead::fill_buf(reader)
+            .map(|buf| buf.to_vec())
+            .map_err(|source| BackupError::ReadFile {
+                path: path.to_path_buf(),
+                source,
+            })
+    };
+    let (mut a_reader, mut b_reader) = (open(a)?, open(b)?);
+    loop {
+        let (a_buf, b_buf) = (fill(&mut a_reader, a)?, fill(&mut b_reader, b)?);
+        let len = a_buf.len().min(b_buf.len());
+        if a_buf[..len] != b_buf[..len] {
+            return Ok(false);
         }
+        if len == 0 {
+            return Ok(a_buf.is_empty() && b_buf.is_empty());
+        }
+        std::io::BufRead::consume(&mut a_reader, len);
+        std::io::BufRead::consume(&mut b_reader, len);
     }
-    Ok(())
 }
 
-pub fn get_backups_sorted(config: &Configuration) -> BackupResult<Vec<(DateTime<Local>, PathBuf)>> {
-    let mut dirs: Vec<(DateTime<Local>, PathBuf)> = std::vec::Vec::new();
-    for entry in read_dir(&config.path)? {
-        let entry = entry?;
-        let filetype = entry.file_type()?;
-        if filetype.is_dir() {
-            match entry.file_name().to_str() {
-                Some(s) => {
-                    let parts = s
-                        .split(['-', ' '])
-                        .map(|a| a.parse::<u32>())
-                        .collect::<Vec<_>>();
-                    if parts.len() != 6 {
-                        continue;
-                    } else if parts.iter().any(|a| a.is_err()) {
-                        continue;
-                    }
-                    dirs.push((
-                        Local
-                            .with_ymd_and_hms(
-                                *parts[0].as_ref().unwrap() as i32,
-                                *parts[1].as_ref().unwrap(),
-                                *parts[2].as_ref().unwrap(),
-                                *parts[3].as_ref().unwrap(),
-                                *parts[4].as_ref().unwrap(),
-                                *parts[5].as_ref().unwrap(),
-                            )
-                            .unwrap(),
-                        entry.path(),
-                    ));
-                }
-                None => {}
-            }
+/// Checks that every file under `src` exists under `dst` with identical contents.
+pub fn verify_copy(src: &Path, dst: &Path) -> BackupResult<()> {
+    for entry in list_dir(src)? {
+        let ty = entry.file_type().map_err(|source| BackupError::ReadDir {
+            path: entry.path(),
+            source,
+        })?;
+        let copy = dst.join(entry.file_name());
+        if ty.is_dir() {
+            verify_copy(&entry.path(), &copy)?;
+        } else if !files_equal(&entry.path(), &copy)? {
+            return Err(BackupError::Mismatch { path: copy });
         }
     }
-
-    dirs.sort_by(|a, b| a.0.cmp(&b.0));
-
-    Ok(dirs)
+    Ok(())
 }
 
-pub fn remove_old_backups(config: &Configuration) -> BackupResult<()> {
-    let dirs = get_backups_sorted(config)?;
-    if dirs.len() > config.max_backups as usize {
-        for i in 0..(dirs.len() - config.max_backups as usize) {
-            match remove_dir_all(&dirs[i].1) {
-                Ok(_) => {}
-                Err(e) => return Err(BackupError::RemoveFolderError(e)),
-            }
+/// Copies a finished snapshot to `mirror`, verifies it and applies the mirror's retention.
+///
+/// A copy that fails part-way or doesn't verify is removed so it is never mistaken for a good one.
+/// The mirror's folder has to exist already, so an unmounted drive isn't filled in on the local
+/// disk.
+fn mirror_snapshot(snapshot: &Path, mirror: &Mirror) -> BackupResult<Vec<PathBuf>> {
+    if !mirror.path.is_dir() {
+        return Err(BackupError::MissingMirror(mirror.path.clone()));
+    }
+    let store = LocalStore::new(&mirror.path);
+    let name = snapshot.file_name().unwrap_or_default().to_string_lossy();
+    let copy = store.path(&name);
+    if let Err(e) = store
+        .write_snapshot(&name, snapshot)
+        .and_then(|_| verify_copy(snapshot, &copy))
+    {
+        if copy.exists() {
+            let _ = delete_backup(&copy);
         }
+        return Err(e);
     }
-    Ok(())
+    Ok(store
+        .prune(mirror.max_backups as usize)?
+        .iter()
+        .map(|name| store.path(name))
+        .collect())
+}
+
+/// Makes a fresh temp folder for one test, holding a `game` folder with a single world in `saves`.
+#[cfg(test)]
+fn test_root(name: &str) -> std::io::Result<PathBuf> {
+    let root = std::env::temp_dir().join(format!("crucible-test-{}-{}", name, std::process::id()));
+    let _ = remove_dir_all(&root);
+    let world = root.join("game").join("saves").join("world");
+    create_dir_all(&world)?;
+    std::fs::write(world.join("level.dat"), b"level")?;
+    Ok(root)
+}
+
+#[test]
+pub fn test_mirror_snapshot() -> std::io::Result<()> {
+    let root = test_root("mirror")?;
+    let snapshot = root.join("primary").join("2024-06-01 12-00-00");
+    create_dir_all(snapshot.join("saves").join("world"))?;
+    std::fs::write(
+        snapshot.join("saves").join("world").join("level.dat"),
+        b"level",
+    )?;
+    create_dir_all(root.join("nas").join("2024-05-01 12-00-00"))?;
+    create_dir_all(root.join("nas").join("2024-05-02 12-00-00"))?;
+
+    let mirror = Mirror {
+        path: root.join("nas"),
+        max_backups: 2,
+    };
+    let pruned = mirror_snapshot(&snapshot, &mirror).unwrap();
+    assert_eq!(pruned, vec![root.join("nas").join("2024-05-01 12-00-00")]);
+    let copy = root.join("nas").join("2024-06-01 12-00-00");
+    assert_eq!(
+        std::fs::read(copy.join("saves").join("world").join("level.dat"))?,
+        b"level"
+    );
+
+    std::fs::write(copy.join("saves").join("world").join("level.dat"), b"levex")?;
+    assert!(matches!(
+        verify_copy(&snapshot, &copy),
+        Err(BackupError::Mismatch { .. })
+    ));
+    std::fs::remove_file(copy.join("saves").join("world").join("level.dat"))?;
+    assert!(matches!(
+        verify_copy(&snapshot, &copy),
+        Err(BackupError::ReadFile { .. })
+    ));
+
+    let unmounted = Mirror {
+        path: root.join("usb"),
+        max_backups: 2,
+    };
+    assert!(matches!(
+        mirror_snapshot(&snapshot, &unmounted),
+        Err(BackupError::MissingMirror(_))
+    ));
+    assert!(!root.join("usb").exists());
+
+    remove_dir_all(&root)
+}
+
+/// Newest modification time among a world's `level.dat` and region (`.mca`) files.
+fn world_mtime(world: &Path) -> Option<std::time::SystemTime> {
+    let mut newest = None;
+    for entry in read_dir(world).ok()?.flatten() {
+        let path = entry.path();
+        let modified = if path.is_dir() {
+            world_mtime(&path)
+        } else if entry.file_name() == "level.dat"
+            || path.extension().is_some_and(|ext| ext == "mca")
+        {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        } else {
+            None
+        };
+        newest = newest.max(modified);
+    }
+    newest
+}
+
+fn is_world(path: &Path) -> bool {
+    path.join("level.dat").is_file()
+}
+
+/// Waits until the world has gone `SETTLE_WINDOW` without a save, giving up after `SETTLE_TIMEOUT`.
+fn wait_for_settle(world: &Path, clock: &dyn Clock) -> bool {
+    let deadline = clock.now() + TimeDelta::from_std(SETTLE_TIMEOUT).unwrap();
+    loop {
+        let settled = match world_mtime(world) {
+            Some(modified) => {
+                clock
+                    .now()
+                    .signed_duration_since(DateTime::<Local>::from(modified))
+                    >= TimeDelta::from_std(SETTLE_WINDOW).unwrap()
+            }
+            None => true,
+        };
+        if settled {
+            return true;
+        } else if clock.now() >= deadline {
+            return false;
+        }
+        clock.sleep(SETTLE_POLL);
+    }
+}
+
+/// Copies one world once it has settled, recopying it if it was saved to mid-copy.
+/// The returned flag is false when the world never held still for a whole copy.
+fn copy_world(
+    world: &Path,
+    dst: &Path,
+    copier: &Copier,
+    clock: &dyn Clock,
+) -> BackupResult<(CopyStats, bool)> {
+    let mut stats = CopyStats::default();
+    for _ in 0..WORLD_COPY_ATTEMPTS {
+        if copier.progress.is_cancelled() {
+            return Err(BackupError::Cancelled);
+        }
+        let settled = wait_for_settle(world, clock);
+        let before = world_mtime(world);
+        if dst.exists() {
+            delete_backup(dst)?;
+        }
+        stats = copier.copy_dir(world, dst)?;
+        if settled && world_mtime(world) == before {
+            return Ok((stats, true));
+        }
+    }
+    Ok((stats, false))
+}
+
+/// Like `copy_dir_all`, but copies any world found at or directly under `src` with `copy_world`.
+/// Worlds that never stabilized are pushed onto `inconsistent`, relative to `src`'s parent.
+fn copy_dir_consistent(
+    src: &Path,
+    dst: &Path,
+    copier: &Copier,
+    clock: &dyn Clock,
+    inconsistent: &mut Vec<PathBuf>,
+) -> BackupResult<CopyStats> {
+    let name = PathBuf::from(src.file_name().unwrap_or_default());
+    if is_world(src) {
+        let (stats, consistent) = copy_world(src, dst, copier, clock)?;
+        if !consistent {
+            inconsistent.push(name);
+        }
+        return Ok(stats);
+    }
+    let mut stats = CopyStats::default();
+    make_dir(dst)?;
+    for entry in list_dir(src)? {
+        let path = entry.path();
+        if copier.excludes(&path, path.is_dir()) {
+            continue;
+        }
+        if is_world(&path) {
+            let (world_stats, consistent) =
+                copy_world(&path, &dst.join(entry.file_name()), copier, clock)?;
+            stats += world_stats;
+            if !consistent {
+                inconsistent.push(name.join(entry.file_name()));
+            }
+        } else if path.is_dir() {
+            stats += copier.copy_dir(&path, &dst.join(entry.file_name()))?;
+        } else {
+            stats += copier.copy_file(&path, &dst.join(entry.file_name()))?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Deletes a single snapshot directory.
+pub fn delete_backup(path: &Path) -> BackupResult<()> {
+    remove_dir_all(path).map_err(|source| BackupError::RemoveDir {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub fn get_backups_sorted(config: &Configuration) -> BackupResult<Vec<(DateTime<Local>, PathBuf)>> {
+    let store = LocalStore::new(&config.path);
+    Ok(store
+        .list()?
+        .iter()
+        .filter_map(|name| Some((parse_snapshot_name(name)?, store.path(name))))
+        .collect())
+}
+
+/// Names of the snapshots at `location`, oldest first, logging a failure to list them.
+pub fn list_backups(
+    location: &Location,
+    config: &Configuration,
+    history: &History,
+) -> Vec<(Location, String)> {
+    match location.open(config).and_then(|store| store.list()) {
+        Ok(names) => names
+            .into_iter()
+            .map(|name| (location.clone(), name))
+            .collect(),
+        Err(e) => {
+            let _ = history.record(&HistoryEntry::new(
+                Local::now(),
+                HistoryKind::Error,
+                format!("unable to list {}: {}", location.display(config), e),
+            ));
+            Vec::new()
+        }
+    }
+}
+
+/// Names of the snapshots on every remote, logging the remotes that can't be listed.
+pub fn list_remote_backups(config: &Configuration, history: &History) -> Vec<(Location, String)> {
+    config
+        .remotes()
+        .into_iter()
+        .flat_map(|remote| list_backups(&Location::Remote(remote), config, history))
+        .collect()
+}
+
+/// Deletes the oldest snapshots beyond `max_backups`, returning the ones removed.
+pub fn remove_old_backups(config: &Configuration) -> BackupResult<Vec<PathBuf>> {
+    let store = LocalStore::new(&config.path);
+    Ok(store
+        .prune(config.max_backups as usize)?
+        .iter()
+        .map(|name| store.path(name))
+        .collect())
 }
 
 #[test]
@@ -567,37 +1658,724 @@ pub fn test_folder_parsing() {
     );
 }
 
-pub fn back_up_files(source: &PathBuf, config: &Configuration) -> BackupResult<PathBuf> {
-    let now = Local::now();
-    let new_dir = config
-        .path
-        .join(now.format("%Y-%m-%d %H-%M-%S").to_string());
-    for i in &config.targets {
-        if source.join(&i).is_dir() {
-            copy_dir_all(source.join(&i), new_dir.join(&i))?;
+/// Writes `report` next to a temporary name and renames it over the old one, so rewriting a
+/// report never leaves it half-written.
+fn write_report(snapshot: &Path, report: &BackupReport) -> BackupResult<()> {
+    let report_path = snapshot.join(REPORT_FILE);
+    let temp_path = snapshot.join(format!("{}.tmp", REPORT_FILE));
+    std::fs::File::create(&temp_path)
+        .and_then(|file| to_writer_pretty(file, report).map_err(std::io::Error::from))
+        .and_then(|_| std::fs::rename(&temp_path, &report_path))
+        .map_err(|source| {
+            let _ = std::fs::remove_file(&temp_path);
+            BackupError::WriteFile {
+                path: report_path,
+                source,
+            }
+        })
+}
+
+/// Rewrites the report of a finished snapshot, adding a failure to its warnings rather than
+/// failing the backup.
+fn update_report(snapshot: &Path, report: &mut BackupReport) {
+    if let Err(e) = write_report(snapshot, report) {
+        report
+            .warnings
+            .push(format!("could not update the report: {}", e));
+    }
+}
+
+pub fn read_report(snapshot: &Path) -> Option<BackupReport> {
+    let file = std::fs::File::open(snapshot.join(REPORT_FILE)).ok()?;
+    from_reader(file).ok()
+}
+
+pub fn back_up_files(
+    source: &PathBuf,
+    config: &Configuration,
+    progress: &Arc<CopyProgress>,
+    clock: &dyn Clock,
+) -> BackupResult<(PathBuf, BackupReport)> {
+    let now = clock.now();
+    let name = snapshot_name(&now);
+    let store = LocalStore::new(&config.path);
+    let new_dir = store.path(&name);
+    // Everything is written under a staging name and only renamed once complete, so a crash
+    // never leaves something that looks like a finished snapshot.
+    let partial = store.staging_path(&name);
+    let passphrase = match (config.encrypt, &config.passphrase) {
+        (true, None) => return Err(CryptoError::NoPassphrase.into()),
+        (true, Some(passphrase)) => Some(passphrase),
+        (false, _) => None,
+    };
+    if partial.exists() {
+        delete_backup(&partial)?;
+    }
+    // Files are sealed as they're copied, so no plaintext is ever written anywhere.
+    let crypt = match passphrase {
+        Some(passphrase) => {
+            let key =
+                make_dir(&partial).and_then(|_| Ok(SnapshotKey::create(passphrase, &partial)?));
+            match key {
+                Ok(key) => Some(Crypt::Seal(Arc::new(key), partial.clone())),
+                Err(e) => {
+                    if partial.exists() {
+                        let _ = delete_backup(&partial);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        None => None,
+    };
+    let copiers = config
+        .targets
+        .iter()
+        .map(|i| {
+            let target = source.join(i);
+            Ok(config
+                .copier(progress)
+                .with_filter(config.filter_for(i, &target)?)
+                .with_crypt(crypt.clone()))
+        })
+        .collect::<Vec<BackupResult<Copier>>>();
+    let mut total = CopyStats::default();
+    for (i, copier) in config.targets.iter().zip(&copiers) {
+        // Missing or unreadable targets and invalid patterns are reported by the copy below.
+        let Ok(copier) = copier else { continue };
+        if let Ok(stats) = copier.measure(&source.join(i)) {
+            total += stats;
+        }
+    }
+    progress.set_total(total);
+    let mut report = BackupReport::default();
+    for (i, copier) in config.targets.iter().zip(copiers) {
+        let target = source.join(i);
+        if !target.exists() {
+            report.skipped.push(i.clone());
+            continue;
+        }
+        let copier = match copier {
+            Ok(copier) => copier,
+            Err(e) => {
+                report.failed.push(TargetFailure {
+                    target: i.clone(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let result = if target.is_dir() && config.consistent_worlds {
+            copy_dir_consistent(
+                &target,
+                &partial.join(i),
+                &copier,
+                clock,
+                &mut report.inconsistent,
+            )
+        } else if target.is_dir() {
+            copier.copy_dir(&target, &partial.join(i))
         } else {
-            create_dir_all(new_dir.join(&i).parent().unwrap())?;
-            copy(source.join(&i), new_dir.join(&i))?;
+            make_dir(partial.join(i).parent().unwrap())
+                .and_then(|_| copier.copy_file(&target, &partial.join(i)))
+        };
+        match result {
+            Ok(stats) => {
+                report.copied.push(i.clone());
+                report.stats += stats;
+            }
+            Err(BackupError::Cancelled) => {
+                if partial.exists() {
+                    delete_backup(&partial)?;
+                }
+                return Err(BackupError::Cancelled);
+            }
+            Err(e) => report.failed.push(TargetFailure {
+                target: i.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    // A cancel that came in after the last file still counts.
+    if progress.is_cancelled() {
+        if partial.exists() {
+            delete_backup(&partial)?;
         }
+        return Err(BackupError::Cancelled);
+    }
+    if report.copied.is_empty() {
+        if partial.exists() {
+            delete_backup(&partial)?;
+        }
+        return Err(BackupError::NothingCopied(
+            report.skipped.len(),
+            report.failed.len(),
+        ));
+    }
+    // The report goes in before the snapshot is finished, so every finished one has one.
+    if let Err(e) =
+        write_report(&partial, &report).and_then(|_| store.write_snapshot(&name, &partial))
+    {
+        let _ = delete_backup(&partial);
+        return Err(e);
+    }
+    // The snapshot is finished, so anything going wrong from here on is only a warning; failing
+    // would have the backup retried, and every retry leave another snapshot.
+    match remove_old_backups(config) {
+        Ok(pruned) => report.pruned = pruned,
+        Err(e) => report
+            .warnings
+            .push(format!("could not remove old backups: {}", e)),
+    }
+    if !report.pruned.is_empty() || !report.warnings.is_empty() {
+        update_report(&new_dir, &mut report);
+    }
+    Ok((new_dir, report))
+}
+
+/// Runs one backup end to end: holds server saves over RCON when configured, copies the targets,
+/// and records the outcome in `history`.
+pub fn run_backup(
+    source: &PathBuf,
+    config: &Configuration,
+    progress: &Arc<CopyProgress>,
+    clock: &dyn Clock,
+    history: &History,
+) -> BackupResult<(PathBuf, BackupReport)> {
+    let _running = progress.begin(Operation::Backup);
+    let started = clock.now();
+    if let Some(command) = &config.hooks.pre_backup {
+        if let Err(e) = run_hook(
+            command,
+            &hook_env("backup", "pre", source, config),
+            config.hooks.timeout,
+        ) {
+            let e = BackupError::Vetoed(e);
+            let _ = history.record(&HistoryEntry::new(
+                started,
+                HistoryKind::Error,
+                format!("backup failed: {}", e),
+            ));
+            notify(
+                config,
+                history,
+                Notification::failure(started, e.to_string()),
+            );
+            return Err(e);
+        }
+    }
+    // Cancelled while the pre-hook ran, before the server or the backup folder were touched.
+    if progress.is_cancelled() {
+        let _ = history.record(&HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Cancel,
+            "backup cancelled before copying started",
+        ));
+        return Err(BackupError::Cancelled);
+    }
+    let mut warnings = Vec::new();
+    let pause = match &config.rcon {
+        Some(rcon) => match SavePause::begin(rcon) {
+            Ok(pause) => Some(pause),
+            Err(e) => {
+                warnings.push(format!("could not pause server saves: {}", e));
+                None
+            }
+        },
+        None => None,
+    };
+    let result = back_up_files(source, config, progress, clock);
+    if let Some(pause) = pause {
+        if let Err(e) = pause.end() {
+            warnings.push(format!("could not resume server saves: {}", e));
+        }
+    }
+    if let Some(command) = &config.hooks.post_backup {
+        let mut env = hook_env("backup", "post", source, config);
+        if let Ok((snapshot, _)) = &result {
+            env.push(("CRUCIBLE_SNAPSHOT", snapshot.display().to_string()));
+        }
+        env.extend(result_env(&result));
+        if let Err(e) = run_hook(command, &env, config.hooks.timeout) {
+            warnings.push(e.to_string());
+        }
+    }
+
+    match result {
+        Ok((snapshot, mut report)) => {
+            // Replicas are copied with the report as it stands, so it has to be complete first.
+            report.warnings.extend(warnings);
+            if !report.warnings.is_empty() {
+                update_report(&snapshot, &mut report);
+            }
+            // The snapshot itself is finished, so a cancel from here on only skips the copies
+            // not yet started.
+            report.mirrors = config
+                .mirrors
+                .iter()
+                .map(|mirror| {
+                    let result = match progress.is_cancelled() {
+                        true => Err(BackupError::Cancelled),
+                        false => mirror_snapshot(&snapshot, mirror),
+                    };
+                    match result {
+                        Ok(pruned) => MirrorOutcome {
+                            path: mirror.path.clone(),
+                            error: None,
+                            pruned,
+                        },
+                        Err(e) => MirrorOutcome {
+                            path: mirror.path.clone(),
+                            error: Some(e.to_string()),
+                            pruned: Vec::new(),
+                        },
+                    }
+                })
+                .collect();
+            for remote in config.remotes() {
+                report.mirrors.push(match progress.is_cancelled() {
+                    true => MirrorOutcome {
+                        path: PathBuf::from(remote.display()),
+                        error: Some(BackupError::Cancelled.to_string()),
+                        pruned: Vec::new(),
+                    },
+                    false => upload_to_remote(&snapshot, &remote),
+                });
+            }
+            if !report.mirrors.is_empty() {
+                update_report(&snapshot, &mut report);
+            }
+            let _ = history.record_backup(started, clock.now(), &snapshot, &report);
+            notify(
+                config,
+                history,
+                Notification::success(clock.now(), snapshot.clone(), &report),
+            );
+            if !report.pruned.is_empty() {
+                notify(
+                    config,
+                    history,
+                    Notification::prune(clock.now(), &report.pruned),
+                );
+            }
+            for mirror in report.mirrors.iter().filter(|m| m.error.is_some()) {
+                notify(
+                    config,
+                    history,
+                    Notification::failure(
+                        clock.now(),
+                        format!(
+                            "mirror to '{}' failed: {}",
+                            mirror.path.display(),
+                            mirror.error.as_deref().unwrap_or_default()
+                        ),
+                    ),
+                );
+            }
+            Ok((snapshot, report))
+        }
+        Err(BackupError::Cancelled) => {
+            let _ = history.record(&HistoryEntry::timed(
+                started,
+                clock.now(),
+                HistoryKind::Cancel,
+                "backup cancelled; the partial snapshot was removed",
+            ));
+            Err(BackupError::Cancelled)
+        }
+        Err(e) => {
+            for warning in warnings {
+                let _ = history.record(&HistoryEntry::new(started, HistoryKind::Error, warning));
+            }
+            let _ = history.record(&HistoryEntry::timed(
+                started,
+                clock.now(),
+                HistoryKind::Error,
+                format!("backup failed: {}", e),
+            ));
+            notify(
+                config,
+                history,
+                Notification::failure(clock.now(), e.to_string()),
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Uploads a finished snapshot, checks the remote holds all of it and applies the remote's
+/// retention, reported like a mirror.
+fn upload_to_remote(snapshot: &Path, remote: &Remote) -> MirrorOutcome {
+    let name = snapshot.file_name().unwrap_or_default().to_string_lossy();
+    let result = remote.open().and_then(|store| {
+        let sent = store.write_snapshot(&name, snapshot)?;
+        let stored = store.stat(&name)?;
+        if (stored.files, stored.bytes) != (sent.files, sent.bytes) {
+            return Err(BackupError::Mismatch {
+                path: PathBuf::from(format!("{}/{}", remote.display(), name)),
+            });
+        }
+        store.prune(remote.max_backups() as usize)
+    });
+    match result {
+        Ok(pruned) => MirrorOutcome {
+            path: PathBuf::from(remote.display()),
+            error: None,
+            pruned: pruned
+                .iter()
+                .map(|name| PathBuf::from(format!("{}/{}", remote.display(), name)))
+                .collect(),
+        },
+        Err(e) => MirrorOutcome {
+            path: PathBuf::from(remote.display()),
+            error: Some(e.to_string()),
+            pruned: Vec::new(),
+        },
+    }
+}
+
+/// Restores the snapshot `name` from `location` with the restore hooks around it, and records the
+/// outcome in `history`.
+///
+/// Snapshots the store keeps as local folders are restored in place; the rest are read out to a
+/// scratch folder first.
+pub fn run_restore(
+    minecraft: &PathBuf,
+    location: &Location,
+    name: &str,
+    config: &Configuration,
+    progress: &Arc<CopyProgress>,
+    clock: &dyn Clock,
+    history: &History,
+) -> BackupResult<CopyStats> {
+    let _running = progress.begin(Operation::Restore);
+    let staging = std::env::temp_dir().join(format!("crucible-restore-{}", name));
+    let snapshot = location
+        .open(config)
+        .and_then(|store| match store.local_path(name) {
+            Some(path) => Ok(path),
+            None => {
+                if staging.exists() {
+                    delete_backup(&staging)?;
+                }
+                store.read_snapshot(name, &staging).map(|_| staging.clone())
+            }
+        });
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            let _ = history.record(&HistoryEntry::new(
+                clock.now(),
+                HistoryKind::Error,
+                format!("restore failed: {}", e),
+            ));
+            if staging.exists() {
+                let _ = delete_backup(&staging);
+            }
+            return Err(e);
+        }
+    };
+    let result = match progress.is_cancelled() {
+        true => {
+            let _ = history.record(&HistoryEntry::new(
+                clock.now(),
+                HistoryKind::Cancel,
+                "restore cancelled before it started; the game was left alone",
+            ));
+            Err(BackupError::Cancelled)
+        }
+        false => restore_with_hooks(minecraft, &snapshot, config, progress, clock, history),
+    };
+    if snapshot == staging {
+        let _ = delete_backup(&staging);
+    }
+    result
+}
+
+fn restore_with_hooks(
+    minecraft: &PathBuf,
+    snapshot: &PathBuf,
+    config: &Configuration,
+    progress: &Arc<CopyProgress>,
+    clock: &dyn Clock,
+    history: &History,
+) -> BackupResult<CopyStats> {
+    let started = clock.now();
+    let mut env = hook_env("restore", "pre", minecraft, config);
+    env.push(("CRUCIBLE_SNAPSHOT", snapshot.display().to_string()));
+    if let Some(command) = &config.hooks.pre_restore {
+        if let Err(e) = run_hook(command, &env, config.hooks.timeout) {
+            let e = BackupError::Vetoed(e);
+            let _ = history.record(
+                &HistoryEntry::new(
+                    started,
+                    HistoryKind::Error,
+                    format!("restore failed: {}", e),
+                )
+                .with_snapshot(snapshot),
+            );
+            notify(
+                config,
+                history,
+                Notification::failure(started, format!("restore failed: {}", e)),
+            );
+            return Err(e);
+        }
+    }
+
+    let result = restore_backup(minecraft, snapshot, config, progress);
+    let entry = match &result {
+        Ok(stats) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Restore,
+            "restored into the game directory",
+        )
+        .with_stats(*stats),
+        Err(BackupError::Cancelled) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Cancel,
+            "restore cancelled; the targets may be only partly restored",
+        ),
+        Err(e) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Error,
+            format!("restore failed: {}", e),
+        ),
+    };
+    let _ = history.record(&entry.with_snapshot(snapshot));
+    notify(
+        config,
+        history,
+        match &result {
+            Ok(stats) => Notification::restore(clock.now(), snapshot.clone(), *stats),
+            Err(e) => Notification::failure(clock.now(), format!("restore failed: {}", e)),
+        },
+    );
+
+    if let Some(command) = &config.hooks.post_restore {
+        let mut env = hook_env("restore", "post", minecraft, config);
+        env.push(("CRUCIBLE_SNAPSHOT", snapshot.display().to_string()));
+        env.extend(result_env(&result));
+        if let Err(e) = run_hook(command, &env, config.hooks.timeout) {
+            let _ = history.record(
+                &HistoryEntry::new(clock.now(), HistoryKind::Error, e.to_string())
+                    .with_snapshot(snapshot),
+            );
+        }
+    }
+    result
+}
+
+/// Sends `notification` to the subscribed webhooks, logging the ones that fail.
+fn notify(config: &Configuration, history: &History, notification: Notification) {
+    for e in notify_all(&config.webhooks, &notification) {
+        let _ = history.record(&HistoryEntry::new(
+            notification.at,
+            HistoryKind::Error,
+            e.to_string(),
+        ));
+    }
+}
+
+/// Variables every hook gets: what is happening, and where.
+fn hook_env(
+    event: &str,
+    phase: &str,
+    minecraft: &Path,
+    config: &Configuration,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("CRUCIBLE_EVENT", event.to_string()),
+        ("CRUCIBLE_PHASE", phase.to_string()),
+        ("CRUCIBLE_PROFILE", config.instance.clone()),
+        ("CRUCIBLE_MINECRAFT_DIR", minecraft.display().to_string()),
+        ("CRUCIBLE_BACKUP_DIR", config.path.display().to_string()),
+    ]
+}
+
+/// Variables post-hooks get on top of `hook_env`: how the operation went.
+fn result_env<T>(result: &BackupResult<T>) -> Vec<(&'static str, String)> {
+    match result {
+        Ok(_) => vec![("CRUCIBLE_RESULT", String::from("success"))],
+        Err(e) => vec![
+            ("CRUCIBLE_RESULT", String::from("failure")),
+            ("CRUCIBLE_ERROR", e.to_string()),
+        ],
     }
-    remove_old_backups(config)?;
-    Ok(new_dir)
 }
 
 pub fn restore_backup(
     minecraft: &PathBuf,
     source: &PathBuf,
     config: &Configuration,
-) -> CodeResult<()> {
+    progress: &Arc<CopyProgress>,
+) -> BackupResult<CopyStats> {
+    let crypt = match is_encrypted(source) {
+        true => {
+            let passphrase = config
+                .passphrase
+                .as_deref()
+                .ok_or(CryptoError::NoPassphrase)?;
+            let key = SnapshotKey::open(passphrase, source)?;
+            // Every file is checked before any is written, so a wrong passphrase or a damaged
+            // file leaves the game alone.
+            verify_files(source, source, Some(&key), progress)?;
+            Some(Crypt::Open(Arc::new(key), source.clone()))
+        }
+        false => None,
+    };
+    let copier = config.copier(progress).with_crypt(crypt);
+    let mut total = CopyStats::default();
+    for i in &config.targets {
+        if let Ok(stats) = measure(&source.join(i)) {
+            total += stats;
+        }
+    }
+    progress.set_total(total);
+    let mut stats = CopyStats::default();
     for i in &config.targets {
-        if source.join(&i).is_dir() {
-            copy_dir_all(source.join(&i), minecraft.join(&i))?;
+        if !source.join(i).exists() {
+            // Targets missing at backup time were never copied; leave the game's copy alone.
+            continue;
+        }
+        if source.join(i).is_dir() {
+            stats += copier.copy_dir(&source.join(i), &minecraft.join(i))?;
         } else {
-            create_dir_all(minecraft.join(&i).parent().unwrap())?;
-            copy(source.join(&i), minecraft.join(&i))?;
+            make_dir(minecraft.join(i).parent().unwrap())?;
+            stats += copier.copy_file(&source.join(i), &minecraft.join(i))?;
         }
     }
-    Ok(())
+    Ok(stats)
+}
+
+/// Reads back every file of a snapshot, checking each one against its key if it is encrypted,
+/// and checks that they add up to what its report says was copied.
+pub fn verify_snapshot(
+    snapshot: &Path,
+    config: &Configuration,
+    progress: &CopyProgress,
+) -> BackupResult<CopyStats> {
+    let report = read_report(snapshot).ok_or_else(|| BackupError::ReadFile {
+        path: snapshot.join(REPORT_FILE),
+        source: std::io::ErrorKind::NotFound.into(),
+    })?;
+    let key = match is_encrypted(snapshot) {
+        true => {
+            let passphrase = config
+                .passphrase
+                .as_deref()
+                .ok_or(CryptoError::NoPassphrase)?;
+            Some(SnapshotKey::open(passphrase, snapshot)?)
+        }
+        false => None,
+    };
+    progress.set_total(report.stats);
+    let stats = verify_files(snapshot, snapshot, key.as_ref(), progress)?;
+    if (stats.files, stats.bytes) != (report.stats.files, report.stats.bytes) {
+        return Err(BackupError::Mismatch {
+            path: snapshot.to_path_buf(),
+        });
+    }
+    Ok(stats)
+}
+
+/// Reads every file under `dir` in `snapshot`, authenticating each one against `key` if given.
+fn verify_files(
+    snapshot: &Path,
+    dir: &Path,
+    key: Option<&SnapshotKey>,
+    progress: &CopyProgress,
+) -> BackupResult<CopyStats> {
+    let mut stats = CopyStats::default();
+    for entry in list_dir(dir)? {
+        let path = entry.path();
+        if path.is_dir() {
+            stats += verify_files(snapshot, &path, key, progress)?;
+            continue;
+        }
+        // Only the snapshot's contents were copied, not its key file or report.
+        if dir == snapshot && (entry.file_name() == KEY_FILE || entry.file_name() == REPORT_FILE) {
+            continue;
+        }
+        if progress.is_cancelled() {
+            return Err(BackupError::Cancelled);
+        }
+        let bytes = match key {
+            Some(key) => key.check_file(snapshot, &path)?,
+            None => std::fs::read(&path)
+                .map(|bytes| bytes.len() as u64)
+                .map_err(|source| BackupError::ReadFile { path, source })?,
+        };
+        let file = CopyStats {
+            files: 1,
+            bytes,
+            ..Default::default()
+        };
+        progress.add(file);
+        stats += file;
+    }
+    Ok(stats)
+}
+
+/// Checks `passphrase` against the newest local encrypted snapshot, so a typo doesn't quietly
+/// start a second passphrase. Returns whether there was one to check it against.
+pub fn check_passphrase(config: &Configuration, passphrase: &str) -> BackupResult<bool> {
+    let newest = get_backups_sorted(config)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .map(|(_, path)| path)
+        .find(|path| is_encrypted(path));
+    match newest {
+        Some(snapshot) => {
+            SnapshotKey::open(passphrase, &snapshot)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Verifies `snapshot` and records how it went in `history`.
+pub fn run_verify(
+    snapshot: &Path,
+    config: &Configuration,
+    progress: &Arc<CopyProgress>,
+    clock: &dyn Clock,
+    history: &History,
+) -> BackupResult<CopyStats> {
+    let _running = progress.begin(Operation::Verify);
+    let started = clock.now();
+    let result = verify_snapshot(snapshot, config, progress);
+    let entry = match &result {
+        Ok(stats) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Verify,
+            match is_encrypted(snapshot) {
+                true => "every file authenticated and the totals match the report",
+                false => "every file read back and the totals match the report",
+            },
+        )
+        .with_stats(*stats),
+        Err(BackupError::Cancelled) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Cancel,
+            "verify cancelled",
+        ),
+        Err(e) => HistoryEntry::timed(
+            started,
+            clock.now(),
+            HistoryKind::Error,
+            format!("verify failed: {}", e),
+        ),
+    };
+    let _ = history.record(&entry.with_snapshot(snapshot));
+    result
 }
 
 #[test]
@@ -605,19 +2383,26 @@ pub fn test_back_up_files() {
     let config = Configuration {
         frequency: Duration::from_secs(5),
         path: PathBuf::from(r"C:\TEMP\backups"),
-        targets: TO_COPY.map(|entry| entry.to_string()).to_vec(),
+        targets: vec![String::from(r"example\a")],
         max_backups: 5,
+        ..Configuration::default()
     };
+    let clock = FakeClock::new(Local::now());
     create_dir_all(r"C:\TEMP\target\example\a").unwrap();
     for _ in 0..7 {
-        match back_up_files(&PathBuf::from(r"C:\TEMP\target"), &config) {
-            Ok(p) => println!("{}", p.display()),
+        match back_up_files(
+            &PathBuf::from(r"C:\TEMP\target"),
+            &config,
+            &Arc::default(),
+            &clock,
+        ) {
+            Ok((p, _)) => println!("{}", p.display()),
             Err(e) => {
                 println!("Error: {}", e);
                 assert!(false);
             }
         }
-        std::thread::sleep(Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
     }
     assert_eq!(
         read_dir(config.path)
@@ -642,6 +2427,7 @@ pub fn test_restore_backup() -> std::io::Result<()> {
             String::from(r"example\b"),
         ],
         max_backups: 5,
+        ..Configuration::default()
     };
 
     create_dir_all(r"C:\TEMP\target\example\a")?;
@@ -653,10 +2439,20 @@ pub fn test_restore_backup() -> std::io::Result<()> {
     let mut file_b = File::create(r"C:\Temp\target\example\b\soup.txt")?;
     file_b.write_all(b"Beef stew.")?;
 
-    match back_up_files(&PathBuf::from(r"C:\TEMP\target"), &config) {
-        Ok(p) => {
+    match back_up_files(
+        &PathBuf::from(r"C:\TEMP\target"),
+        &config,
+        &Arc::default(),
+        &SystemClock,
+    ) {
+        Ok((p, _)) => {
             remove_dir_all(r"C:\TEMP\target\example")?;
-            match restore_backup(&PathBuf::from(r"C:\TEMP\target"), &p, &config) {
+            match restore_backup(
+                &PathBuf::from(r"C:\TEMP\target"),
+                &p,
+                &config,
+                &Arc::default(),
+            ) {
                 Ok(_) => {}
                 Err(e) => {
                     println!("{:?}", e);
@@ -673,6 +2469,532 @@ pub fn test_restore_backup() -> std::io::Result<()> {
     Ok(())
 }
 
+#[test]
+pub fn test_back_up_files_partial() -> std::io::Result<()> {
+    let root = test_root("partial")?;
+    std::fs::write(root.join("game").join("options.txt"), b"fov:90")?;
+
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![
+            String::from("options.txt"),
+            String::from("saves"),
+            String::from("journeymap"),
+        ],
+        ..Configuration::default()
+    };
+    let (snapshot, report) =
+        back_up_files(&root.join("game"), &config, &Arc::default(), &SystemClock).unwrap();
+    assert_eq!(report.copied, vec!["options.txt", "saves"]);
+    assert_eq!(report.skipped, vec!["journeymap"]);
+    assert!(report.failed.is_empty());
+    assert_eq!((report.stats.files, report.stats.bytes), (2, 11));
+    // Only the finished snapshot is left in the backup folder.
+    assert_eq!(read_dir(&config.path)?.count(), 1);
+    assert!(snapshot
+        .join("saves")
+        .join("world")
+        .join("level.dat")
+        .is_file());
+    assert_eq!(read_report(&snapshot), Some(report));
+
+    let config = Configuration {
+        targets: vec![String::from("journeymap")],
+        ..config
+    };
+    assert!(matches!(
+        back_up_files(&root.join("game"), &config, &Arc::default(), &SystemClock),
+        Err(BackupError::NothingCopied(1, 0))
+    ));
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_consistent_worlds() -> std::io::Result<()> {
+    let root = test_root("consistency")?;
+    let world = root.join("game").join("saves").join("world");
+    create_dir_all(world.join("region"))?;
+    std::fs::write(world.join("region").join("r.0.0.mca"), b"region")?;
+    std::fs::write(world.join("session.lock"), b"lock")?;
+    std::fs::write(root.join("game").join("saves").join("notes.txt"), b"notes")?;
+
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        consistent_worlds: true,
+        ..Configuration::default()
+    };
+
+    // The fake clock only moves when the copy waits, so a fresh save settles after SETTLE_WINDOW.
+    let start = Local::now();
+    let clock = FakeClock::new(start);
+    let (snapshot, report) =
+        back_up_files(&root.join("game"), &config, &Arc::default(), &clock).unwrap();
+    assert!(report.inconsistent.is_empty());
+    assert_eq!(report.stats.files, 4);
+    assert!(snapshot
+        .join("saves")
+        .join("world")
+        .join("region")
+        .join("r.0.0.mca")
+        .is_file());
+    assert!(clock.now() > start);
+    assert!(clock.now() < start + TimeDelta::from_std(SETTLE_TIMEOUT).unwrap());
+
+    // A world saved "in the future" relative to the clock never settles and gets flagged.
+    let clock = FakeClock::new(Local::now() - TimeDelta::hours(1));
+    let (_, report) = back_up_files(&root.join("game"), &config, &Arc::default(), &clock).unwrap();
+    assert_eq!(report.inconsistent, vec![Path::new("saves").join("world")]);
+    assert_eq!(report.copied, vec!["saves"]);
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_cancelled_backup() -> std::io::Result<()> {
+    /// Cancels the backup the first time it waits for a world to settle.
+    struct CancellingClock(FakeClock, Arc<CopyProgress>);
+
+    impl Clock for CancellingClock {
+        fn now(&self) -> DateTime<Local> {
+            self.0.now()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.1.cancel();
+            self.0.sleep(duration);
+        }
+    }
+
+    let root = test_root("cancel")?;
+    std::fs::write(root.join("game").join("options.txt"), b"fov:90")?;
+
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("options.txt"), String::from("saves")],
+        consistent_worlds: true,
+        ..Configuration::default()
+    };
+    let progress = Arc::new(CopyProgress::default());
+    let clock = CancellingClock(FakeClock::new(Local::now()), Arc::clone(&progress));
+    assert!(matches!(
+        back_up_files(&root.join("game"), &config, &progress, &clock),
+        Err(BackupError::Cancelled)
+    ));
+    // options.txt was already copied, but nothing of the snapshot is left behind.
+    assert!(read_dir(&config.path)?.next().is_none());
+    assert!(progress.get().is_none());
+
+    // A cancel from before any copying starts, e.g. during the pre-hook, isn't lost.
+    struct CancelledAtStart(Arc<CopyProgress>);
+
+    impl Clock for CancelledAtStart {
+        fn now(&self) -> DateTime<Local> {
+            self.0.cancel();
+            Local::now()
+        }
+
+        fn sleep(&self, _: Duration) {}
+    }
+
+    let history = History::new(root.join(HISTORY_FILE));
+    let clock = CancelledAtStart(Arc::clone(&progress));
+    assert!(matches!(
+        run_backup(&root.join("game"), &config, &progress, &clock, &history),
+        Err(BackupError::Cancelled)
+    ));
+    assert!(read_dir(&config.path)?.next().is_none());
+    assert_eq!(history.load()?[0].kind, HistoryKind::Cancel);
+    assert!(progress.get().is_none());
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_run_backup_pauses_saves() -> std::io::Result<()> {
+    let root = test_root("rcon")?;
+
+    let (address, server) = crate::rcon::mock_rcon_server("secret");
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        rcon: Some(RconConfig {
+            address,
+            password: String::from("secret"),
+            broadcast: None,
+        }),
+        ..Configuration::default()
+    };
+    let history = History::new(root.join(HISTORY_FILE));
+    let (_, report) = run_backup(
+        &root.join("game"),
+        &config,
+        &Arc::default(),
+        &SystemClock,
+        &history,
+    )
+    .unwrap();
+    assert!(report.warnings.is_empty());
+    assert_eq!(
+        server.join().unwrap(),
+        vec!["save-off", "save-all flush", "save-on"]
+    );
+
+    // An unreachable server is a warning, not a reason to skip the backup.
+    create_dir_all(root.join("nas"))?;
+    let config = Configuration {
+        rcon: Some(RconConfig {
+            address: String::from("127.0.0.1:1"),
+            password: String::from("secret"),
+            broadcast: None,
+        }),
+        mirrors: vec![Mirror {
+            path: root.join("nas"),
+            max_backups: 5,
+        }],
+        ..config
+    };
+    let (snapshot, report) = run_backup(
+        &root.join("game"),
+        &config,
+        &Arc::default(),
+        &SystemClock,
+        &history,
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(read_report(&snapshot), Some(report.clone()));
+    // The mirror got the report with the warning in it.
+    let mirrored = read_report(&root.join("nas").join(snapshot.file_name().unwrap())).unwrap();
+    assert_eq!(mirrored.warnings, report.warnings);
+    assert_eq!(history.load()?.len(), 3);
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_run_backup_hooks() -> std::io::Result<()> {
+    let root = test_root("hooks")?;
+    let history = History::new(root.join(HISTORY_FILE));
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        hooks: Hooks {
+            pre_backup: Some(String::from("exit 1")),
+            ..Hooks::default()
+        },
+        ..Configuration::default()
+    };
+    assert!(matches!(
+        run_backup(
+            &root.join("game"),
+            &config,
+            &Arc::default(),
+            &SystemClock,
+            &history
+        ),
+        Err(BackupError::Vetoed(_))
+    ));
+    assert!(!root.join("backups").exists());
+
+    let log = root.join("post.txt");
+    let config = Configuration {
+        hooks: Hooks {
+            post_backup: Some(match cfg!(windows) {
+                true => format!(
+                    "echo %CRUCIBLE_RESULT% %CRUCIBLE_SNAPSHOT%> \"{}\"",
+                    log.display()
+                ),
+                false => format!(
+                    "echo \"$CRUCIBLE_RESULT $CRUCIBLE_SNAPSHOT\" > '{}'",
+                    log.display()
+                ),
+            }),
+            ..Hooks::default()
+        },
+        ..config
+    };
+    let (snapshot, _) = run_backup(
+        &root.join("game"),
+        &config,
+        &Arc::default(),
+        &SystemClock,
+        &history,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&log)?.trim_end(),
+        format!("success {}", snapshot.display())
+    );
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_run_backup_s3() -> std::io::Result<()> {
+    let root = test_root("run-s3")?;
+    let (endpoint, _) = crate::s3::mock_s3_server();
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        s3: Some(S3Config {
+            endpoint,
+            region: String::from("us-east-1"),
+            bucket: String::from("backups"),
+            prefix: String::new(),
+            access_key: String::from("access"),
+            secret_key: String::from("secret"),
+            max_backups: 5,
+        }),
+        ..Configuration::default()
+    };
+    let history = History::new(root.join(HISTORY_FILE));
+    let (snapshot, report) = run_backup(
+        &root.join("game"),
+        &config,
+        &Arc::default(),
+        &SystemClock,
+        &history,
+    )
+    .unwrap();
+    assert_eq!(report.mirrors.len(), 1);
+    assert_eq!(report.mirrors[0].error, None);
+
+    let mut app = App::new();
+    app.configuration = config.clone();
+    app.history = history.clone();
+    app.refresh_local_backups();
+    app.set_remote_backups(list_remote_backups(&config, &history));
+    let name = snapshot.file_name().unwrap().to_string_lossy().to_string();
+    assert_eq!(app.backups.len(), 2);
+    assert!(matches!(app.backups[0].0, Location::Local));
+    assert!(matches!(app.backups[1].0, Location::Remote(_)));
+    assert_eq!(app.backups[1].1, name);
+
+    std::fs::remove_dir_all(root.join("game").join("saves"))?;
+    let (remote, _) = &app.backups[1];
+    run_restore(
+        &root.join("game"),
+        remote,
+        &name,
+        &config,
+        &Arc::default(),
+        &SystemClock,
+        &history,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read(
+            root.join("game")
+                .join("saves")
+                .join("world")
+                .join("level.dat")
+        )?,
+        b"level"
+    );
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_verify_snapshot() -> std::io::Result<()> {
+    let root = test_root("verify")?;
+    let config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        ..Configuration::default()
+    };
+    let (snapshot, _) =
+        back_up_files(&root.join("game"), &config, &Arc::default(), &SystemClock).unwrap();
+    let history = History::new(root.join(HISTORY_FILE));
+    let stats = run_verify(&snapshot, &config, &Arc::default(), &SystemClock, &history).unwrap();
+    assert_eq!((stats.files, stats.bytes), (1, 5));
+    assert_eq!(history.load()?[0].kind, HistoryKind::Verify);
+
+    // A file that lost its contents no longer adds up to what the report says was copied.
+    let level = snapshot.join("saves").join("world").join("level.dat");
+    std::fs::write(&level, b"")?;
+    assert!(matches!(
+        verify_snapshot(&snapshot, &config, &CopyProgress::default()),
+        Err(BackupError::Mismatch { .. })
+    ));
+    std::fs::write(&level, b"level")?;
+    std::fs::remove_file(snapshot.join(REPORT_FILE))?;
+    assert!(matches!(
+        run_verify(&snapshot, &config, &Arc::default(), &SystemClock, &history),
+        Err(BackupError::ReadFile { .. })
+    ));
+    assert_eq!(history.load()?[1].kind, HistoryKind::Error);
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_encrypted_backup() -> std::io::Result<()> {
+    let root = test_root("encrypted")?;
+    let mut config = Configuration {
+        path: root.join("backups"),
+        targets: vec![String::from("saves")],
+        encrypt: true,
+        ..Configuration::default()
+    };
+    assert!(matches!(
+        back_up_files(&root.join("game"), &config, &Arc::default(), &SystemClock),
+        Err(BackupError::Crypto(CryptoError::NoPassphrase))
+    ));
+
+    config.passphrase = Some(String::from("hunter2"));
+    let (snapshot, report) =
+        back_up_files(&root.join("game"), &config, &Arc::default(), &SystemClock).unwrap();
+    assert_eq!(report.stats.files, 1);
+    let sealed = std::fs::read(snapshot.join("saves").join("world").join("level.dat"))?;
+    assert_ne!(sealed, b"level");
+    assert!(check_passphrase(&config, "hunter2").unwrap());
+    assert!(matches!(
+        check_passphrase(&config, "hunter3"),
+        Err(BackupError::Crypto(CryptoError::WrongPassphrase(_)))
+    ));
+    let empty = Configuration {
+        path: root.join("empty"),
+        ..config.clone()
+    };
+    assert!(!check_passphrase(&empty, "hunter3").unwrap());
+    assert!(verify_snapshot(&snapshot, &config, &CopyProgress::default()).is_ok());
+
+    std::fs::write(
+        root.join("game")
+            .join("saves")
+            .join("world")
+            .join("level.dat"),
+        b"changed",
+    )?;
+    let wrong = Configuration {
+        passphrase: Some(String::from("hunter3")),
+        ..config.clone()
+    };
+    assert!(matches!(
+        verify_snapshot(&snapshot, &wrong, &CopyProgress::default()),
+        Err(BackupError::Crypto(CryptoError::WrongPassphrase(_)))
+    ));
+    assert!(matches!(
+        restore_backup(&root.join("game"), &snapshot, &wrong, &Arc::default()),
+        Err(BackupError::Crypto(CryptoError::WrongPassphrase(_)))
+    ));
+    assert_eq!(
+        std::fs::read(
+            root.join("game")
+                .join("saves")
+                .join("world")
+                .join("level.dat")
+        )?,
+        b"changed"
+    );
+
+    // A damaged file is caught before anything in the game is touched.
+    let sealed_path = snapshot.join("saves").join("world").join("level.dat");
+    let mut damaged = sealed.clone();
+    *damaged.last_mut().unwrap() ^= 1;
+    std::fs::write(&sealed_path, &damaged)?;
+    assert!(matches!(
+        restore_backup(&root.join("game"), &snapshot, &config, &Arc::default()),
+        Err(BackupError::Crypto(CryptoError::Corrupt(_)))
+    ));
+    std::fs::write(&sealed_path, &sealed)?;
+
+    restore_backup(&root.join("game"), &snapshot, &config, &Arc::default()).unwrap();
+    assert_eq!(
+        std::fs::read(
+            root.join("game")
+                .join("saves")
+                .join("world")
+                .join("level.dat")
+        )?,
+        b"level"
+    );
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_copy_errors_carry_paths() -> std::io::Result<()> {
+    let root = test_root("errors")?;
+    create_dir_all(root.join("src"))?;
+    std::fs::write(root.join("src").join("level.dat"), b"level")?;
+    std::fs::write(root.join("blocker"), b"not a directory")?;
+
+    match copy_dir_all(root.join("missing"), root.join("dst")) {
+        Err(BackupError::ReadDir { path, .. }) => assert_eq!(path, root.join("missing")),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match copy_dir_all(root.join("src"), root.join("blocker").join("dst")) {
+        Err(BackupError::CreateDir { path, .. }) => {
+            assert_eq!(path, root.join("blocker").join("dst"))
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let error = delete_backup(&root.join("missing")).unwrap_err();
+    assert!(error.to_string().contains("missing"));
+
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_delete_listed_backup() -> std::io::Result<()> {
+    let root = test_root("delete-listed")?;
+    create_dir_all(root.join("backups").join("2024-06-01 12-00-00"))?;
+    let mut app = App::new();
+    app.configuration.path = root.join("backups");
+    app.history = History::new(root.join(HISTORY_FILE));
+    app.refresh_local_backups();
+    assert_eq!(app.backups.len(), 1);
+
+    // A snapshot that can't be deleted stays listed, with the reason shown and logged.
+    app.backups
+        .push((Location::Local, String::from("2024-06-02 12-00-00")));
+    app.delete_listed_backup(1);
+    assert_eq!(app.backups.len(), 2);
+    assert!(app.backups_error.is_some());
+    assert_eq!(
+        app.history.load().unwrap().last().unwrap().kind,
+        HistoryKind::Error
+    );
+
+    app.delete_listed_backup(0);
+    assert_eq!(app.backups.len(), 1);
+    assert_eq!(app.backups_error, None);
+    assert!(!root.join("backups").join("2024-06-01 12-00-00").exists());
+    remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_load_selected_report() -> std::io::Result<()> {
+    let root = test_root("selected-report")?;
+    let snapshot = root.join("backups").join("2024-06-01 12-00-00");
+    create_dir_all(&snapshot)?;
+    let mut app = App::new();
+    app.configuration.path = root.join("backups");
+    app.history = History::new(root.join(HISTORY_FILE));
+    app.refresh_local_backups();
+
+    app.load_selected_report(Some(0));
+    assert_eq!(
+        app.selected_report,
+        Some((String::from("2024-06-01 12-00-00"), None))
+    );
+    // Kept while the selection stays put, and read again once the list is refreshed.
+    let report = BackupReport {
+        copied: vec![String::from("saves")],
+        ..BackupReport::default()
+    };
+    write_report(&snapshot, &report).unwrap();
+    app.load_selected_report(Some(0));
+    assert_eq!(app.selected_report.as_ref().unwrap().1, None);
+    app.refresh_local_backups();
+    app.load_selected_report(Some(0));
+    assert_eq!(app.selected_report.as_ref().unwrap().1, Some(report));
+    remove_dir_all(&root)
+}
+
 #[test]
 pub fn test_pathbuf_join() -> std::io::Result<()> {
     let path = PathBuf::from(r"C:\TEMP\BCG");
@@ -685,7 +3007,30 @@ pub fn test_pathbuf_join() -> std::io::Result<()> {
 pub struct App {
     pub current_screen: CurrentScreen,
     pub configuration: Configuration,
-    pub next_backup: DateTime<Local>,
+    /// When the worker will next back up on its own, if it knows.
+    pub next_backup: Option<DateTime<Local>>,
+    pub backup_status: BackupStatus,
+    pub history: History,
+    /// Whether the instance is running, or `None` when game-aware scheduling is off.
+    pub game_running: Option<bool>,
+    /// Snapshots listed on the Backups screen, local ones first.
+    pub backups: Vec<(Location, String)>,
+    /// The report of the local snapshot selected on the Backups screen, by snapshot name, so it
+    /// isn't read again on every redraw.
+    pub selected_report: Option<(String, Option<BackupReport>)>,
+    /// Whether the remotes are still being listed for the Backups screen.
+    pub remotes_loading: bool,
+    /// The last preview asked for on the Targets screen.
+    pub target_preview: Option<TargetPreview>,
+    /// Why the last delete on the Backups screen failed, shown until the screen is left.
+    pub backups_error: Option<String>,
+    /// Restore waiting for the worker to pick it up.
+    pub pending_restore: Option<(Location, String)>,
+    /// Local snapshot waiting for the worker to verify it.
+    pub pending_verify: Option<PathBuf>,
+    pub last_restore: Option<RestoreStatus>,
+    /// Shared with the worker, so the UI sees how far its copy has got and can cancel it.
+    pub progress: Arc<CopyProgress>,
 }
 
 impl App {
@@ -693,7 +3038,22 @@ impl App {
         App {
             current_screen: CurrentScreen::Main,
             configuration: Configuration::default(),
-            next_backup: DateTime::from_timestamp_nanos(0).into(),
+            next_backup: None,
+            backup_status: BackupStatus::Idle,
+            history: History::new(match get_config_path() {
+                Ok(p) => p.with_file_name(HISTORY_FILE),
+                Err(_) => PathBuf::from(HISTORY_FILE),
+            }),
+            game_running: None,
+            backups: Vec::new(),
+            selected_report: None,
+            remotes_loading: false,
+            target_preview: None,
+            backups_error: None,
+            pending_restore: None,
+            pending_verify: None,
+            last_restore: None,
+            progress: Arc::default(),
         }
     }
 
@@ -727,6 +3087,105 @@ impl App {
         Ok(())
     }
 
+    /// Lists the snapshots in the backup folder again, keeping the remote ones as they were.
+    pub fn refresh_local_backups(&mut self) {
+        let local = list_backups(&Location::Local, &self.configuration, &self.history);
+        self.backups
+            .retain(|(location, _)| !matches!(location, Location::Local));
+        self.backups.splice(0..0, local);
+        // A backup that just finished may have rewritten a report, e.g. after mirroring.
+        self.selected_report = None;
+    }
+
+    /// Reads the report of the snapshot at `selected` on the Backups screen, unless it's the one
+    /// already loaded.
+    pub fn load_selected_report(&mut self, selected: Option<usize>) {
+        let name = match selected.and_then(|index| self.backups.get(index)) {
+            Some((Location::Local, name)) => name,
+            _ => return,
+        };
+        if self
+            .selected_report
+            .as_ref()
+            .is_some_and(|(loaded, _)| loaded == name)
+        {
+            return;
+        }
+        let report = read_report(&LocalStore::new(&self.configuration.path).path(name));
+        self.selected_report = Some((name.clone(), report));
+    }
+
+    /// Replaces the remote entries on the Backups screen once they have been listed.
+    pub fn set_remote_backups(&mut self, remote: Vec<(Location, String)>) {
+        self.backups
+            .retain(|(location, _)| matches!(location, Location::Local));
+        self.backups.extend(remote);
+        self.remotes_loading = false;
+    }
+
+    /// Deletes the snapshot listed at `index` on the Backups screen.
+    ///
+    /// A failure is logged and kept in `backups_error`, and the snapshot stays listed so the
+    /// delete can be tried again.
+    pub fn delete_listed_backup(&mut self, index: usize) {
+        let Some((location, name)) = self.backups.get(index).cloned() else {
+            return;
+        };
+        let place = location.display(&self.configuration);
+        match location
+            .open(&self.configuration)
+            .and_then(|store| store.delete(&name))
+        {
+            Ok(()) => {
+                self.backups.remove(index);
+                self.backups_error = None;
+                let _ = self.history.record(&HistoryEntry::new(
+                    Local::now(),
+                    HistoryKind::Prune,
+                    format!("deleted {} from {}", name, place),
+                ));
+            }
+            Err(e) => {
+                let message = format!("unable to delete {} from {}: {}", name, place, e);
+                let _ = self.history.record(&HistoryEntry::new(
+                    Local::now(),
+                    HistoryKind::Error,
+                    message.clone(),
+                ));
+                self.backups_error = Some(message);
+            }
+        }
+    }
+
+    /// Removes snapshots a crash left half-written in the backup folder or on a mirror.
+    pub fn clean_partial_snapshots(&self) {
+        let roots = std::iter::once(&self.configuration.path)
+            .chain(self.configuration.mirrors.iter().map(|mirror| &mirror.path));
+        for root in roots.filter(|root| root.is_dir()) {
+            match LocalStore::new(root).clean_partial() {
+                Ok(removed) => {
+                    for path in removed {
+                        let _ = self.history.record(
+                            &HistoryEntry::new(
+                                Local::now(),
+                                HistoryKind::Prune,
+                                "removed an unfinished snapshot",
+                            )
+                            .with_snapshot(&path),
+                        );
+                    }
+                }
+                Err(e) => {
+                    let _ = self.history.record(&HistoryEntry::new(
+                        Local::now(),
+                        HistoryKind::Error,
+                        format!("unable to clean up unfinished snapshots: {}", e),
+                    ));
+                }
+            }
+        }
+    }
+
     pub fn set_view(&mut self, view: CurrentScreen) {
         self.current_screen = view;
     }
@@ -0,0 +1,582 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{list_dir, make_dir, BackupError, BackupResult, CopyStats},
+    crypto::SnapshotKey,
+    filter::TargetFilter,
+};
+
+/// Threads used when the configuration leaves the count at 0: one per CPU core.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
+
+pub fn copy_file(from: &Path, to: &Path) -> BackupResult<u64> {
+    std::fs::copy(from, to).map_err(|source| BackupError::Copy {
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+        source,
+    })
+}
+
+/// Whether files are cloned copy-on-write (a reflink) instead of copied byte by byte.
+///
+/// A reflink shares the original's blocks until either copy changes, so on btrfs or XFS a backup
+/// takes next to no time or space.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum ReflinkMode {
+    /// Clone where the filesystem supports it and copy everywhere else.
+    #[default]
+    Auto,
+    /// Fail files that can't be cloned instead of copying them.
+    Always,
+    Never,
+}
+
+impl ReflinkMode {
+    pub fn next(self) -> ReflinkMode {
+        match self {
+            ReflinkMode::Auto => ReflinkMode::Always,
+            ReflinkMode::Always => ReflinkMode::Never,
+            ReflinkMode::Never => ReflinkMode::Auto,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReflinkMode::Auto => "auto",
+            ReflinkMode::Always => "always",
+            ReflinkMode::Never => "never",
+        }
+    }
+}
+
+/// Clones `from` over `to` with `FICLONE`, returning the size of the clone.
+///
+/// The clone is made in a sibling file and renamed over `to` only once it succeeds, so a failed
+/// clone never leaves `to` truncated.
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> std::io::Result<u64> {
+    use std::os::fd::AsRawFd;
+    let src = std::fs::File::open(from)?;
+    let metadata = src.metadata()?;
+    let mut name = to.as_os_str().to_owned();
+    name.push(".crucible-clone");
+    let temp = PathBuf::from(name);
+    let clone = || {
+        let dst = std::fs::File::create(&temp)?;
+        // SAFETY: both descriptors belong to files that stay open until after the call.
+        if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        dst.set_permissions(metadata.permissions())?;
+        std::fs::rename(&temp, to)
+    };
+    if let Err(e) = clone() {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(metadata.len())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_: &Path, _: &Path) -> std::io::Result<u64> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Number of files under `path` and their total size, or just `path` itself if it's a file.
+pub fn measure(path: &Path) -> BackupResult<CopyStats> {
+    measure_filtered(path, None)
+}
+
+fn measure_filtered(path: &Path, filter: Option<&TargetFilter>) -> BackupResult<CopyStats> {
+    let metadata = std::fs::metadata(path).map_err(|source| BackupError::ReadDir {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    if !metadata.is_dir() {
+        return Ok(CopyStats {
+            files: 1,
+            bytes: metadata.len(),
+            ..Default::default()
+        });
+    }
+    let mut stats = CopyStats::default();
+    for entry in list_dir(path)? {
+        let metadata = entry.metadata().map_err(|source| BackupError::ReadDir {
+            path: entry.path(),
+            source,
+        })?;
+        if filter.is_some_and(|filter| filter.excludes(&entry.path(), metadata.is_dir())) {
+            continue;
+        }
+        if metadata.is_dir() {
+            stats += measure_filtered(&entry.path(), filter)?;
+        } else {
+            stats.files += 1;
+            stats.bytes += metadata.len();
+        }
+    }
+    Ok(stats)
+}
+
+/// What a running copy is for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operation {
+    Backup,
+    Restore,
+    Verify,
+}
+
+impl Operation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Backup => "Backing up",
+            Operation::Restore => "Restoring",
+            Operation::Verify => "Verifying",
+        }
+    }
+}
+
+/// A look at a running copy, as taken by `CopyProgress::get`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProgressReport {
+    pub operation: Operation,
+    pub done: CopyStats,
+    pub total: CopyStats,
+    /// The file most recently started, if any has been yet.
+    pub current: Option<PathBuf>,
+}
+
+impl ProgressReport {
+    /// Share of the copy done, by bytes, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        let (done, total) = match self.total.bytes {
+            0 => (self.done.files, self.total.files),
+            _ => (self.done.bytes, self.total.bytes),
+        };
+        match total {
+            0 => 0.0,
+            // A world recopied because it changed mid-copy counts twice.
+            _ => (done as f64 / total as f64).min(1.0),
+        }
+    }
+}
+
+/// How far the running backup or restore has got, shared between the thread doing it and the UI.
+#[derive(Default, Debug)]
+pub struct CopyProgress {
+    files_done: AtomicU64,
+    bytes_done: AtomicU64,
+    files_total: AtomicU64,
+    bytes_total: AtomicU64,
+    cancelled: AtomicBool,
+    /// What the operation is and the file most recently started, while one is running.
+    running: Mutex<Option<(Operation, Option<PathBuf>)>>,
+}
+
+/// Marks the operation as finished when dropped, however it ended.
+pub struct ProgressGuard<'a>(&'a CopyProgress);
+
+impl Drop for ProgressGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() = None;
+    }
+}
+
+impl CopyProgress {
+    /// Marks `operation` as running until the guard is dropped, through every phase and not
+    /// just the copy, and clears any earlier cancel.
+    pub fn begin(&self, operation: Operation) -> ProgressGuard<'_> {
+        self.cancelled.store(false, Ordering::Relaxed);
+        self.set_total(CopyStats::default());
+        *self.running.lock().unwrap() = Some((operation, None));
+        ProgressGuard(self)
+    }
+
+    /// Starts counting a copy expected to move `total`.
+    pub fn set_total(&self, total: CopyStats) {
+        self.files_done.store(0, Ordering::Relaxed);
+        self.bytes_done.store(0, Ordering::Relaxed);
+        self.files_total.store(total.files, Ordering::Relaxed);
+        self.bytes_total.store(total.bytes, Ordering::Relaxed);
+    }
+
+    fn set_current(&self, path: &Path) {
+        if let Some((_, current)) = &mut *self.running.lock().unwrap() {
+            *current = Some(path.to_path_buf());
+        }
+    }
+
+    /// Makes the running operation fail with `BackupError::Cancelled` before its next file or
+    /// phase. It stays cancelled until the next one begins.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, stats: CopyStats) {
+        self.files_done.fetch_add(stats.files, Ordering::Relaxed);
+        self.bytes_done.fetch_add(stats.bytes, Ordering::Relaxed);
+    }
+
+    /// Where the running operation is at, or `None` if there isn't one.
+    pub fn get(&self) -> Option<ProgressReport> {
+        let (operation, current) = self.running.lock().unwrap().clone()?;
+        Some(ProgressReport {
+            operation,
+            done: CopyStats {
+                files: self.files_done.load(Ordering::Relaxed),
+                bytes: self.bytes_done.load(Ordering::Relaxed),
+                ..Default::default()
+            },
+            total: CopyStats {
+                files: self.files_total.load(Ordering::Relaxed),
+                bytes: self.bytes_total.load(Ordering::Relaxed),
+                ..Default::default()
+            },
+            current,
+        })
+    }
+}
+
+struct Job {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// Copies folders with a bounded pool of threads, counting every file into `progress`.
+///
+/// The folder structure is created up front, then the files are handed out to the threads one
+/// at a time. The first failure stops the threads from picking up more files and is returned,
+/// like a sequential copy would.
+#[derive(Clone, Default)]
+pub struct Copier {
+    /// Files copied at once; 0 uses `default_threads`.
+    pub threads: usize,
+    pub reflink: ReflinkMode,
+    pub progress: Arc<CopyProgress>,
+    /// Leaves matching files out of `copy_dir`.
+    pub filter: Option<Arc<TargetFilter>>,
+    /// Encrypts or decrypts every file on its way through.
+    pub crypt: Option<Crypt>,
+}
+
+/// How a copier passes files through a snapshot's key, so plaintext never has to be staged
+/// anywhere but the source and the final destination.
+#[derive(Clone)]
+pub enum Crypt {
+    /// Seals files written into the snapshot at the given path.
+    Seal(Arc<SnapshotKey>, PathBuf),
+    /// Opens files read out of the snapshot at the given path.
+    Open(Arc<SnapshotKey>, PathBuf),
+}
+
+impl Crypt {
+    fn copy_file(&self, from: &Path, to: &Path) -> BackupResult<u64> {
+        Ok(match self {
+            Crypt::Seal(key, snapshot) => key.seal_file(snapshot, from, to)?,
+            Crypt::Open(key, snapshot) => key.open_file(snapshot, from, to)?,
+        })
+    }
+}
+
+impl Copier {
+    pub fn new(threads: usize, reflink: ReflinkMode, progress: Arc<CopyProgress>) -> Copier {
+        Copier {
+            threads,
+            reflink,
+            progress,
+            filter: None,
+            crypt: None,
+        }
+    }
+
+    pub fn with_crypt(self, crypt: Option<Crypt>) -> Copier {
+        Copier { crypt, ..self }
+    }
+
+    pub fn with_filter(self, filter: Option<TargetFilter>) -> Copier {
+        Copier {
+            filter: filter.map(Arc::new),
+            ..self
+        }
+    }
+
+    /// Whether the filter leaves `path` out of the copy.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        match &self.filter {
+            Some(filter) => filter.excludes(path, is_dir),
+            None => false,
+        }
+    }
+
+    /// Like `measure`, but leaving out what the filter excludes.
+    pub fn measure(&self, path: &Path) -> BackupResult<CopyStats> {
+        measure_filtered(path, self.filter.as_deref())
+    }
+
+    fn threads(&self) -> usize {
+        match self.threads {
+            0 => default_threads(),
+            threads => threads,
+        }
+    }
+
+    pub fn copy_file(&self, from: &Path, to: &Path) -> BackupResult<CopyStats> {
+        let copied = |bytes, reflinked| CopyStats {
+            files: 1,
+            bytes,
+            reflinked,
+        };
+        if self.progress.is_cancelled() {
+            return Err(BackupError::Cancelled);
+        }
+        self.progress.set_current(from);
+        let stats = match (&self.crypt, self.reflink) {
+            (Some(crypt), _) => copied(crypt.copy_file(from, to)?, 0),
+            (None, ReflinkMode::Never) => copied(copy_file(from, to)?, 0),
+            (None, mode) => match reflink(from, to) {
+                Ok(bytes) => copied(bytes, 1),
+                Err(source) if mode == ReflinkMode::Always => {
+                    return Err(BackupError::Reflink {
+                        from: from.to_path_buf(),
+                        to: to.to_path_buf(),
+                        source,
+                    })
+                }
+                Err(_) => copied(copy_file(from, to)?, 0),
+            },
+        };
+        self.progress.add(stats);
+        Ok(stats)
+    }
+
+    /// Copies everything under `src` into `dst`, or the file `src` into the folder `dst`.
+    pub fn copy_dir(&self, src: &Path, dst: &Path) -> BackupResult<CopyStats> {
+        let mut jobs = Vec::new();
+        self.plan(src, dst, &mut jobs)?;
+        self.run(&jobs)
+    }
+
+    /// Creates the folders of the copy and lists the files to copy into them.
+    fn plan(&self, src: &Path, dst: &Path, jobs: &mut Vec<Job>) -> BackupResult<()> {
+        make_dir(dst)?;
+        if src.is_file() {
+            jobs.push(Job {
+                from: src.to_path_buf(),
+                to: dst.join(match src.file_name() {
+                    Some(v) => v,
+                    None => std::ffi::OsStr::new("unknown"),
+                }),
+            });
+            return Ok(());
+        }
+        for entry in list_dir(src)? {
+            let ty = entry.file_type().map_err(|source| BackupError::ReadDir {
+                path: entry.path(),
+                source,
+            })?;
+            if self.excludes(&entry.path(), ty.is_dir()) {
+                continue;
+            }
+            if ty.is_dir() {
+                self.plan(&entry.path(), &dst.join(entry.file_name()), jobs)?;
+            } else {
+                jobs.push(Job {
+                    from: entry.path(),
+                    to: dst.join(entry.file_name()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&self, jobs: &[Job]) -> BackupResult<CopyStats> {
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        // Keeps the failure earliest in walk order, so the error doesn't depend on timing.
+        let first_error: Mutex<Option<(usize, BackupError)>> = Mutex::new(None);
+        let work = || {
+            let mut stats = CopyStats::default();
+            while !failed.load(Ordering::Relaxed) {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let job = match jobs.get(index) {
+                    Some(job) => job,
+                    None => break,
+                };
+                match self.copy_file(&job.from, &job.to) {
+                    Ok(copied) => stats += copied,
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        let mut first_error = first_error.lock().unwrap();
+                        let earlier = match &*first_error {
+                            Some((first, _)) => index < *first,
+                            None => true,
+                        };
+                        if earlier {
+                            *first_error = Some((index, e));
+                        }
+                    }
+                }
+            }
+            stats
+        };
+        let threads = self.threads().min(jobs.len());
+        let mut stats = CopyStats::default();
+        if threads <= 1 {
+            stats = work();
+        } else {
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+                for worker in workers {
+                    stats += worker.join().unwrap();
+                }
+            });
+        }
+        match first_error.into_inner().unwrap() {
+            Some((_, e)) => Err(e),
+            None => Ok(stats),
+        }
+    }
+}
+
+#[test]
+pub fn test_parallel_copy() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-parallel-copy");
+    let _ = std::fs::remove_dir_all(&root);
+    let src = root.join("src");
+    for region in 0..8 {
+        let dir = src.join("journeymap").join(format!("region-{}", region));
+        std::fs::create_dir_all(&dir)?;
+        for tile in 0..25 {
+            std::fs::write(
+                dir.join(format!("{}.png", tile)),
+                format!("{}:{}", region, tile),
+            )?;
+        }
+    }
+    std::fs::create_dir_all(src.join("empty"))?;
+    let total = measure(&src).unwrap();
+    assert_eq!(total.files, 200);
+
+    let copier = Copier::new(4, ReflinkMode::Never, Arc::default());
+    let progress = Arc::clone(&copier.progress);
+    let guard = progress.begin(Operation::Backup);
+    progress.set_total(total);
+    let stats = copier.copy_dir(&src, &root.join("dst")).unwrap();
+    assert_eq!(stats, total);
+    let report = progress.get().unwrap();
+    assert_eq!((report.done, report.total), (total, total));
+    assert_eq!(report.ratio(), 1.0);
+    assert!(report.current.unwrap().starts_with(&src));
+    drop(guard);
+    assert_eq!(progress.get(), None);
+    assert_eq!(
+        std::fs::read_to_string(
+            root.join("dst")
+                .join("journeymap")
+                .join("region-7")
+                .join("24.png")
+        )?,
+        "7:24"
+    );
+    assert!(root.join("dst").join("empty").is_dir());
+
+    // Cancelling stops the copy before the next file, and holds until another operation begins.
+    let guard = progress.begin(Operation::Restore);
+    progress.cancel();
+    assert!(matches!(
+        copier.copy_dir(&src, &root.join("cancelled")),
+        Err(BackupError::Cancelled)
+    ));
+    drop(guard);
+    assert!(progress.is_cancelled());
+    let _guard = progress.begin(Operation::Backup);
+    assert!(!progress.is_cancelled());
+
+    // A file that can't be written fails the whole copy with the same error as before.
+    let blocked = root.join("blocked");
+    std::fs::create_dir_all(blocked.join("journeymap").join("region-3").join("9.png"))?;
+    match copier.copy_dir(&src, &blocked) {
+        Err(BackupError::Copy { to, .. }) => assert!(to.ends_with("9.png")),
+        other => panic!("expected a copy error, got {:?}", other.map(|_| ())),
+    }
+
+    std::fs::remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_reflink_modes() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-reflink");
+    let _ = std::fs::remove_dir_all(&root);
+    let src = root.join("src");
+    std::fs::create_dir_all(src.join("saves"))?;
+    std::fs::write(src.join("saves").join("level.dat"), b"level")?;
+    std::fs::write(src.join("options.txt"), b"fov:90")?;
+
+    // Whether files can be cloned depends on the filesystem the tests run on, so it's found out
+    // first and each mode is checked against that.
+    let cloning = reflink(&src.join("options.txt"), &root.join("probe.txt")).is_ok();
+
+    let copy =
+        |mode, dst: &str| Copier::new(2, mode, Arc::default()).copy_dir(&src, &root.join(dst));
+    assert_eq!(copy(ReflinkMode::Never, "never").unwrap().reflinked, 0);
+    let auto = copy(ReflinkMode::Auto, "auto").unwrap();
+    assert_eq!((auto.files, auto.bytes), (2, 11));
+    assert_eq!(
+        std::fs::read(root.join("auto").join("saves").join("level.dat"))?,
+        b"level"
+    );
+    let report = crate::app::BackupReport {
+        stats: auto,
+        ..Default::default()
+    };
+    match cloning {
+        true => {
+            assert_eq!(auto.reflinked, 2);
+            assert!(report.summary().ends_with(", 2 reflinked)"));
+            assert_eq!(copy(ReflinkMode::Always, "always").unwrap().reflinked, 2);
+        }
+        // Auto falls back to copying; Always refuses to.
+        false => {
+            assert_eq!(auto.reflinked, 0);
+            assert!(!report.summary().contains("reflinked"));
+            assert!(matches!(
+                copy(ReflinkMode::Always, "always"),
+                Err(BackupError::Reflink { .. })
+            ));
+        }
+    }
+
+    // A clone that fails in Always mode must leave an existing destination as it was.
+    let existing = root.join("existing.txt");
+    std::fs::write(&existing, b"keep me")?;
+    let copier = Copier::new(1, ReflinkMode::Always, Arc::default());
+    assert!(copier
+        .copy_file(&root.join("missing.txt"), &existing)
+        .is_err());
+    assert_eq!(std::fs::read(&existing)?, b"keep me");
+    let result = copier.copy_file(&src.join("options.txt"), &existing);
+    assert_eq!(result.is_ok(), cloning);
+    match cloning {
+        true => assert_eq!(std::fs::read(&existing)?, b"fov:90"),
+        false => assert_eq!(std::fs::read(&existing)?, b"keep me"),
+    }
+    assert!(!root.join("existing.txt.crucible-clone").exists());
+
+    std::fs::remove_dir_all(&root)
+}
@@ -0,0 +1,377 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{
+        generic_array::GenericArray,
+        stream::{DecryptorBE32, EncryptorBE32},
+        Aead, KeyInit, Payload,
+    },
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Written into every encrypted snapshot, with what it takes to derive the snapshot's key again.
+pub const KEY_FILE: &str = "crucible-key.json";
+/// Supplies the passphrase without the startup prompt, e.g. when running unattended.
+pub const PASSPHRASE_VAR: &str = "CRUCIBLE_PASSPHRASE";
+const MAGIC: &[u8] = b"CRUCENC1";
+/// Starts every sealed file, followed by its nonce and then its chunks.
+const STREAM_MAGIC: &[u8] = b"CRUCENC2";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+/// STREAM keeps the last 5 bytes of the nonce for the chunk counter and last-chunk flag.
+const STREAM_NONCE_LEN: usize = NONCE_LEN - 5;
+/// Plaintext sealed at a time, so memory use doesn't grow with the file.
+const CHUNK_LEN: usize = 64 * 1024;
+const SEALED_CHUNK_LEN: usize = CHUNK_LEN + 16;
+/// Sealed into the key file so a wrong passphrase is caught before any file is read.
+const CHECK: &[u8] = b"crucible";
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("encryption is on but no passphrase has been entered")]
+    NoPassphrase,
+    #[error("wrong passphrase for '{}'", .0.display())]
+    WrongPassphrase(PathBuf),
+    #[error("'{}' is damaged or doesn't belong to this snapshot", .0.display())]
+    Corrupt(PathBuf),
+    #[error("unable to set up the encryption key: {0}")]
+    Key(String),
+    #[error("unable to access '{}': {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+pub type CryptoResult<T> = std::result::Result<T, CryptoError>;
+
+#[derive(Serialize, Deserialize)]
+struct KeyInfo {
+    salt: String,
+    check: String,
+}
+
+fn random<const N: usize>() -> CryptoResult<[u8; N]> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| CryptoError::Key(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> CryptoError + '_ {
+    move |source| CryptoError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Reads up to `len` bytes from `reader`, fewer only at its end.
+fn read_chunk(reader: &mut impl Read, len: usize, path: &Path) -> CryptoResult<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(len);
+    reader
+        .take(len as u64)
+        .read_to_end(&mut chunk)
+        .map_err(io_error(path))?;
+    Ok(chunk)
+}
+
+/// The key of one snapshot, derived from the passphrase and the snapshot's own salt with Argon2id.
+///
+/// Each file is sealed in chunks with XChaCha20-Poly1305 STREAM under a random nonce, bound to its
+/// path inside the snapshot so files can't be swapped around unnoticed. The last chunk is sealed
+/// as such, so a truncated file is caught too.
+pub struct SnapshotKey(XChaCha20Poly1305);
+
+impl SnapshotKey {
+    fn derive(passphrase: &str, salt: &[u8]) -> CryptoResult<SnapshotKey> {
+        let mut key = [0; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| CryptoError::Key(e.to_string()))?;
+        Ok(SnapshotKey(XChaCha20Poly1305::new(&key.into())))
+    }
+
+    /// Derives a fresh key for a new snapshot and writes its key file into `snapshot`.
+    pub fn create(passphrase: &str, snapshot: &Path) -> CryptoResult<SnapshotKey> {
+        let salt = random::<SALT_LEN>()?;
+        let key = SnapshotKey::derive(passphrase, &salt)?;
+        let info = KeyInfo {
+            salt: hex::encode(salt),
+            check: hex::encode(key.seal(KEY_FILE, CHECK)?),
+        };
+        let path = snapshot.join(KEY_FILE);
+        std::fs::write(&path, serde_json::to_vec_pretty(&info).unwrap_or_default())
+            .map_err(io_error(&path))?;
+        Ok(key)
+    }
+
+    /// Derives the key of an existing snapshot, failing with `WrongPassphrase` if it doesn't match.
+    pub fn open(passphrase: &str, snapshot: &Path) -> CryptoResult<SnapshotKey> {
+        let path = snapshot.join(KEY_FILE);
+        let text = std::fs::read_to_string(&path).map_err(io_error(&path))?;
+        let info: KeyInfo =
+            serde_json::from_str(&text).map_err(|_| CryptoError::Corrupt(path.clone()))?;
+        let salt = hex::decode(&info.salt).map_err(|_| CryptoError::Corrupt(path.clone()))?;
+        let check = hex::decode(&info.check).map_err(|_| CryptoError::Corrupt(path.clone()))?;
+        let key = SnapshotKey::derive(passphrase, &salt)?;
+        match key.unseal(KEY_FILE, &check) {
+            Some(plain) if plain == CHECK => Ok(key),
+            _ => Err(CryptoError::WrongPassphrase(snapshot.to_path_buf())),
+        }
+    }
+
+    fn seal(&self, name: &str, plain: &[u8]) -> CryptoResult<Vec<u8>> {
+        let nonce = random::<NONCE_LEN>()?;
+        let sealed = self
+            .0
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plain,
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|e| CryptoError::Key(e.to_string()))?;
+        Ok([MAGIC, &nonce, &sealed].concat())
+    }
+
+    fn unseal(&self, name: &str, data: &[u8]) -> Option<Vec<u8>> {
+        let rest = data.strip_prefix(MAGIC)?;
+        if rest.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        self.0
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: sealed,
+                    aad: name.as_bytes(),
+                },
+            )
+            .ok()
+    }
+
+    /// Encrypts the plain file `from` into `to`, a file inside `snapshot`, returning the size of
+    /// the plaintext.
+    pub fn seal_file(&self, snapshot: &Path, from: &Path, to: &Path) -> CryptoResult<u64> {
+        let relative = to.strip_prefix(snapshot).unwrap_or(to);
+        let name = sealed_name(relative);
+        let nonce = random::<STREAM_NONCE_LEN>()?;
+        let mut reader = File::open(from).map_err(io_error(from))?;
+        let mut writer = BufWriter::new(File::create(to).map_err(io_error(to))?);
+        writer
+            .write_all(&[STREAM_MAGIC, &nonce].concat())
+            .map_err(io_error(to))?;
+        let mut encryptor =
+            EncryptorBE32::from_aead(self.0.clone(), GenericArray::from_slice(&nonce));
+        let mut size = 0;
+        let mut chunk = read_chunk(&mut reader, CHUNK_LEN, from)?;
+        loop {
+            size += chunk.len() as u64;
+            let payload = Payload {
+                msg: &chunk,
+                aad: name.as_bytes(),
+            };
+            // A full chunk may turn out to be the last, which is sealed differently.
+            let next = match chunk.len() {
+                CHUNK_LEN => read_chunk(&mut reader, CHUNK_LEN, from)?,
+                _ => Vec::new(),
+            };
+            if next.is_empty() {
+                let sealed = encryptor
+                    .encrypt_last(payload)
+                    .map_err(|e| CryptoError::Key(e.to_string()))?;
+                writer.write_all(&sealed).map_err(io_error(to))?;
+                break;
+            }
+            let sealed = encryptor
+                .encrypt_next(payload)
+                .map_err(|e| CryptoError::Key(e.to_string()))?;
+            writer.write_all(&sealed).map_err(io_error(to))?;
+            chunk = next;
+        }
+        writer.flush().map_err(io_error(to))?;
+        Ok(size)
+    }
+
+    /// Authenticates and decrypts `path`, a file inside `snapshot`, a chunk at a time, handing
+    /// each chunk's plaintext to `out`, and returns the size of the plaintext.
+    fn open_chunks(
+        &self,
+        snapshot: &Path,
+        path: &Path,
+        mut out: impl FnMut(&[u8]) -> CryptoResult<()>,
+    ) -> CryptoResult<u64> {
+        let relative = path.strip_prefix(snapshot).unwrap_or(path);
+        let name = sealed_name(relative);
+        let corrupt = || CryptoError::Corrupt(path.to_path_buf());
+        let mut reader = File::open(path).map_err(io_error(path))?;
+        let header = read_chunk(&mut reader, STREAM_MAGIC.len() + STREAM_NONCE_LEN, path)?;
+        let nonce = match header.strip_prefix(STREAM_MAGIC) {
+            Some(nonce) if nonce.len() == STREAM_NONCE_LEN => nonce,
+            _ => return Err(corrupt()),
+        };
+        let mut decryptor =
+            DecryptorBE32::from_aead(self.0.clone(), GenericArray::from_slice(nonce));
+        let mut size = 0;
+        let mut chunk = read_chunk(&mut reader, SEALED_CHUNK_LEN, path)?;
+        loop {
+            let payload = Payload {
+                msg: &chunk,
+                aad: name.as_bytes(),
+            };
+            let next = match chunk.len() {
+                SEALED_CHUNK_LEN => read_chunk(&mut reader, SEALED_CHUNK_LEN, path)?,
+                _ => Vec::new(),
+            };
+            if next.is_empty() {
+                let plain = decryptor.decrypt_last(payload).map_err(|_| corrupt())?;
+                out(&plain)?;
+                return Ok(size + plain.len() as u64);
+            }
+            let plain = decryptor.decrypt_next(payload).map_err(|_| corrupt())?;
+            out(&plain)?;
+            size += plain.len() as u64;
+            chunk = next;
+        }
+    }
+
+    /// Decrypts `from`, a file inside `snapshot`, into the plain file `to`, returning the size of
+    /// the plaintext. `to` is removed again if `from` turns out to be damaged.
+    pub fn open_file(&self, snapshot: &Path, from: &Path, to: &Path) -> CryptoResult<u64> {
+        let mut writer = BufWriter::new(File::create(to).map_err(io_error(to))?);
+        let result = self
+            .open_chunks(snapshot, from, |plain| {
+                writer.write_all(plain).map_err(io_error(to))
+            })
+            .and_then(|size| writer.flush().map_err(io_error(to)).map(|_| size));
+        if result.is_err() {
+            drop(writer);
+            let _ = std::fs::remove_file(to);
+        }
+        result
+    }
+
+    /// Checks that `path`, a file inside `snapshot`, authenticates without writing its plaintext
+    /// anywhere, returning the size of the plaintext.
+    pub fn check_file(&self, snapshot: &Path, path: &Path) -> CryptoResult<u64> {
+        self.open_chunks(snapshot, path, |_| Ok(()))
+    }
+}
+
+/// A file's path inside the snapshot, spelled the same on every platform.
+fn sealed_name(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn is_encrypted(snapshot: &Path) -> bool {
+    snapshot.join(KEY_FILE).exists()
+}
+
+#[test]
+pub fn test_snapshot_key() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-crypto");
+    let _ = std::fs::remove_dir_all(&root);
+    let (plain, snapshot) = (root.join("plain"), root.join("snapshot"));
+    std::fs::create_dir_all(plain.join("saves").join("world"))?;
+    std::fs::create_dir_all(&snapshot)?;
+    std::fs::write(
+        plain.join("saves").join("world").join("level.dat"),
+        b"level",
+    )?;
+    std::fs::write(plain.join("options.txt"), b"fov:90")?;
+
+    let key = SnapshotKey::create("hunter2", &snapshot).unwrap();
+    std::fs::create_dir_all(snapshot.join("saves").join("world"))?;
+    for relative in [Path::new("saves/world/level.dat"), Path::new("options.txt")] {
+        key.seal_file(&snapshot, &plain.join(relative), &snapshot.join(relative))
+            .unwrap();
+    }
+    assert_eq!(
+        key.check_file(&snapshot, &snapshot.join("options.txt"))
+            .unwrap(),
+        6
+    );
+    assert!(is_encrypted(&snapshot));
+    let sealed = std::fs::read(snapshot.join("options.txt"))?;
+    assert!(sealed.starts_with(STREAM_MAGIC));
+    assert!(!sealed.windows(6).any(|w| w == b"fov:90"));
+
+    assert!(matches!(
+        SnapshotKey::open("hunter3", &snapshot),
+        Err(CryptoError::WrongPassphrase(_))
+    ));
+    let key = SnapshotKey::open("hunter2", &snapshot).unwrap();
+    let restored = root.join("level.dat");
+    key.open_file(
+        &snapshot,
+        &snapshot.join("saves").join("world").join("level.dat"),
+        &restored,
+    )
+    .unwrap();
+    assert_eq!(std::fs::read(&restored)?, b"level");
+
+    // A file moved to another path no longer authenticates.
+    std::fs::rename(
+        snapshot.join("options.txt"),
+        snapshot.join("saves").join("options.txt"),
+    )?;
+    assert!(matches!(
+        key.check_file(&snapshot, &snapshot.join("saves").join("options.txt")),
+        Err(CryptoError::Corrupt(_))
+    ));
+
+    std::fs::remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_sealed_chunks() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-crypto-chunks");
+    let _ = std::fs::remove_dir_all(&root);
+    let snapshot = root.join("snapshot");
+    std::fs::create_dir_all(&snapshot)?;
+    let key = SnapshotKey::create("hunter2", &snapshot).unwrap();
+
+    for (name, len) in [
+        ("empty", 0),
+        ("exact", 2 * CHUNK_LEN),
+        ("long", 2 * CHUNK_LEN + 100),
+    ] {
+        let plain: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        std::fs::write(root.join(name), &plain)?;
+        let sealed = snapshot.join(name);
+        assert_eq!(
+            key.seal_file(&snapshot, &root.join(name), &sealed).unwrap(),
+            len as u64
+        );
+        let restored = root.join(format!("{}-restored", name));
+        key.open_file(&snapshot, &sealed, &restored).unwrap();
+        assert_eq!(std::fs::read(&restored)?, plain, "{}", name);
+    }
+
+    // Dropping whole chunks off the end is caught, as what's left doesn't end in a last chunk.
+    let sealed = std::fs::read(snapshot.join("long"))?;
+    std::fs::write(
+        snapshot.join("long"),
+        &sealed[..STREAM_MAGIC.len() + STREAM_NONCE_LEN + SEALED_CHUNK_LEN],
+    )?;
+    let restored = root.join("truncated");
+    assert!(matches!(
+        key.open_file(&snapshot, &snapshot.join("long"), &restored),
+        Err(CryptoError::Corrupt(_))
+    ));
+    assert!(!restored.exists());
+
+    std::fs::remove_dir_all(&root)
+}
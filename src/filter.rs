@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{
+    app::{list_dir, BackupError, BackupResult, CopyStats},
+    copy::measure,
+};
+
+/// Which files inside a folder target get copied, decided by gitignore-style patterns.
+///
+/// Patterns are matched relative to the target: `session.lock` matches at any depth, `/stats`
+/// only directly inside the target, `DIM-1/` only folders, and `!level.dat` brings back a file an
+/// earlier pattern excluded. As in git, nothing inside an excluded folder can be brought back.
+pub struct TargetFilter(Gitignore);
+
+impl TargetFilter {
+    pub fn new(target: &str, root: &Path, patterns: &[String]) -> BackupResult<TargetFilter> {
+        let invalid = |source| BackupError::Pattern {
+            target: target.to_string(),
+            source,
+        };
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in patterns {
+            builder.add_line(None, pattern).map_err(invalid)?;
+        }
+        Ok(TargetFilter(builder.build().map_err(invalid)?))
+    }
+
+    /// Whether `path`, somewhere under the target, is left out of the copy.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        self.0.matched(path, is_dir).is_ignore()
+    }
+}
+
+/// What a target's patterns leave in and out, for showing before a backup runs.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Preview {
+    pub included: CopyStats,
+    /// Paths relative to the target. An excluded folder is listed once, not file by file.
+    pub excluded: Vec<PathBuf>,
+}
+
+/// File targets are copied whole, so only folders are matched against the patterns.
+pub fn preview(root: &Path, filter: &TargetFilter) -> BackupResult<Preview> {
+    let mut preview = Preview::default();
+    if !root.is_dir() {
+        preview.included = measure(root)?;
+        return Ok(preview);
+    }
+    preview_dir(root, root, filter, &mut preview)?;
+    preview.excluded.sort();
+    Ok(preview)
+}
+
+fn preview_dir(
+    root: &Path,
+    dir: &Path,
+    filter: &TargetFilter,
+    preview: &mut Preview,
+) -> BackupResult<()> {
+    for entry in list_dir(dir)? {
+        let path = entry.path();
+        let metadata = entry.metadata().map_err(|source| BackupError::ReadDir {
+            path: path.clone(),
+            source,
+        })?;
+        if filter.excludes(&path, metadata.is_dir()) {
+            preview
+                .excluded
+                .push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        } else if metadata.is_dir() {
+            preview_dir(root, &path, filter, preview)?;
+        } else {
+            preview.included.files += 1;
+            preview.included.bytes += metadata.len();
+        }
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_target_filter() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-filter");
+    let _ = std::fs::remove_dir_all(&root);
+    let saves = root.join("saves");
+    let world = saves.join("world");
+    std::fs::create_dir_all(world.join("DIM-1").join("region"))?;
+    std::fs::create_dir_all(world.join("stats"))?;
+    std::fs::create_dir_all(world.join("region"))?;
+    std::fs::write(world.join("level.dat"), b"level")?;
+    std::fs::write(world.join("session.lock"), b"lock")?;
+    std::fs::write(world.join("region").join("r.0.0.mca"), b"region")?;
+    std::fs::write(world.join("region").join("r.0.0.mca.tmp"), b"tmp")?;
+    std::fs::write(
+        world.join("DIM-1").join("region").join("r.0.0.mca"),
+        b"nether",
+    )?;
+    std::fs::write(world.join("stats").join("player.json"), b"{}")?;
+    std::fs::write(world.join("stats").join("keep.json"), b"{}")?;
+
+    let patterns = [
+        "session.lock",
+        "*.tmp",
+        "DIM-1/",
+        "**/stats/*",
+        "!**/stats/keep.json",
+    ]
+    .map(String::from);
+    let filter = TargetFilter::new("saves", &saves, &patterns).unwrap();
+    assert!(filter.excludes(&world.join("session.lock"), false));
+    assert!(filter.excludes(&world.join("DIM-1"), true));
+    assert!(!filter.excludes(&world.join("level.dat"), false));
+
+    let preview = preview(&saves, &filter).unwrap();
+    assert_eq!(
+        preview.excluded,
+        vec![
+            Path::new("world").join("DIM-1"),
+            Path::new("world").join("region").join("r.0.0.mca.tmp"),
+            Path::new("world").join("session.lock"),
+            Path::new("world").join("stats").join("player.json"),
+        ]
+    );
+    assert_eq!(preview.included.files, 3);
+
+    // The copy leaves out the same files and never creates excluded folders.
+    let copier = crate::copy::Copier::default().with_filter(Some(filter));
+    assert_eq!(copier.measure(&saves).unwrap(), preview.included);
+    let copied = root.join("copied");
+    let stats = copier.copy_dir(&saves, &copied).unwrap();
+    assert_eq!((stats.files, stats.bytes), (3, preview.included.bytes));
+    assert!(copied
+        .join("world")
+        .join("stats")
+        .join("keep.json")
+        .is_file());
+    assert!(!copied
+        .join("world")
+        .join("stats")
+        .join("player.json")
+        .exists());
+    assert!(!copied.join("world").join("DIM-1").exists());
+
+    assert!(matches!(
+        TargetFilter::new("saves", &saves, &[String::from("{region,DIM-1")]),
+        Err(BackupError::Pattern { .. })
+    ));
+
+    std::fs::remove_dir_all(&root)
+}
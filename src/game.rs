@@ -0,0 +1,101 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+    time::Duration,
+};
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+/// How often the worker checks whether the game is still running while game-aware.
+pub const GAME_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Watches the process list for a Java process launched with `--gameDir` pointing at the instance.
+pub struct GameMonitor {
+    system: System,
+}
+
+impl GameMonitor {
+    pub fn new() -> GameMonitor {
+        GameMonitor {
+            system: System::new(),
+        }
+    }
+
+    pub fn is_running(&mut self, game_dir: &Path) -> bool {
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::new().with_cmd(UpdateKind::OnlyIfNotSet),
+        );
+        self.system
+            .processes()
+            .values()
+            .any(|process| is_game_process(process.name(), process.cmd(), game_dir))
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.replace('/', "\\")
+        .trim_end_matches('\\')
+        .to_lowercase()
+}
+
+/// Whether a process with this executable name and command line is Minecraft running from `game_dir`.
+pub fn is_game_process(name: &OsStr, cmd: &[OsString], game_dir: &Path) -> bool {
+    if !name.to_string_lossy().to_lowercase().starts_with("java") {
+        return false;
+    }
+    let wanted = normalize(&game_dir.to_string_lossy());
+    let mut args = cmd.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        let dir = match arg.strip_prefix("--gameDir=") {
+            Some(dir) => Some(dir.to_string()),
+            None if arg == "--gameDir" => args.next().map(|dir| dir.into_owned()),
+            None => None,
+        };
+        if dir.is_some_and(|dir| normalize(&dir) == wanted) {
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+pub fn test_is_game_process() {
+    let game_dir = Path::new(r"C:\curseforge\minecraft\Instances\BigChadGuys Plus (w Cobblemon)");
+    let cmd = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+
+    assert!(is_game_process(
+        OsStr::new("javaw.exe"),
+        &cmd(&[
+            "javaw.exe",
+            "-Xmx8G",
+            "net.minecraft.client.main.Main",
+            "--gameDir",
+            r"C:\CurseForge\minecraft\Instances\BigChadGuys Plus (w Cobblemon)\",
+        ]),
+        game_dir,
+    ));
+    assert!(is_game_process(
+        OsStr::new("java"),
+        &cmd(&[
+            "java",
+            "--gameDir=C:/curseforge/minecraft/Instances/BigChadGuys Plus (w Cobblemon)",
+        ]),
+        game_dir,
+    ));
+    assert!(!is_game_process(
+        OsStr::new("javaw.exe"),
+        &cmd(&[
+            "javaw.exe",
+            "--gameDir",
+            r"C:\curseforge\minecraft\Instances\Some Other Pack",
+        ]),
+        game_dir,
+    ));
+    assert!(!is_game_process(
+        OsStr::new("notepad.exe"),
+        &cmd(&["notepad.exe", "--gameDir", &game_dir.to_string_lossy()]),
+        game_dir,
+    ));
+}
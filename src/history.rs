@@ -0,0 +1,256 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::prelude::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{BackupReport, CopyStats};
+
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Backup,
+    Skip,
+    Prune,
+    Restore,
+    Verify,
+    Cancel,
+    Error,
+}
+
+impl HistoryKind {
+    /// Every kind, in the order the History screen's filter cycles through them.
+    pub const ALL: [HistoryKind; 7] = [
+        HistoryKind::Backup,
+        HistoryKind::Skip,
+        HistoryKind::Prune,
+        HistoryKind::Restore,
+        HistoryKind::Verify,
+        HistoryKind::Cancel,
+        HistoryKind::Error,
+    ];
+
+    /// The filter after `current`: all kinds, then each kind in turn, then back to all.
+    pub fn next_filter(current: Option<HistoryKind>) -> Option<HistoryKind> {
+        match current {
+            None => Some(HistoryKind::ALL[0]),
+            Some(kind) => HistoryKind::ALL
+                .iter()
+                .position(|k| *k == kind)
+                .and_then(|i| HistoryKind::ALL.get(i + 1))
+                .copied(),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            HistoryKind::Backup => "backup",
+            HistoryKind::Skip => "skip",
+            HistoryKind::Prune => "prune",
+            HistoryKind::Restore => "restore",
+            HistoryKind::Verify => "verify",
+            HistoryKind::Cancel => "cancel",
+            HistoryKind::Error => "error",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub kind: HistoryKind,
+    pub duration: Duration,
+    pub bytes: u64,
+    pub files: u64,
+    pub snapshot: Option<PathBuf>,
+    pub detail: String,
+}
+
+impl HistoryEntry {
+    pub fn new(timestamp: DateTime<Local>, kind: HistoryKind, detail: impl Into<String>) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind,
+            duration: Duration::from_secs(0),
+            bytes: 0,
+            files: 0,
+            snapshot: None,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn timed(
+        started: DateTime<Local>,
+        finished: DateTime<Local>,
+        kind: HistoryKind,
+        detail: impl Into<String>,
+    ) -> Self {
+        HistoryEntry {
+            duration: finished
+                .signed_duration_since(started)
+                .to_std()
+                .unwrap_or(Duration::from_secs(0)),
+            ..HistoryEntry::new(started, kind, detail)
+        }
+    }
+
+    pub fn with_stats(self, stats: CopyStats) -> Self {
+        HistoryEntry {
+            files: stats.files,
+            bytes: stats.bytes,
+            ..self
+        }
+    }
+
+    pub fn with_snapshot(self, snapshot: &Path) -> Self {
+        HistoryEntry {
+            snapshot: Some(snapshot.to_path_buf()),
+            ..self
+        }
+    }
+}
+
+/// Append-only log of everything the worker and the user did, one JSON object per line.
+#[derive(Clone)]
+pub struct History {
+    path: PathBuf,
+    /// Bumped by every `record` on this log or any of its clones.
+    revision: Arc<AtomicUsize>,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> History {
+        History {
+            path,
+            revision: Arc::default(),
+        }
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes());
+        self.revision.fetch_add(1, Ordering::Relaxed);
+        written
+    }
+
+    /// Changes whenever an entry is recorded, so a copy of `load`'s result knows when it's stale.
+    pub fn revision(&self) -> usize {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    /// Records a finished backup along with the targets it skipped, the snapshots it pruned and how
+    /// each mirror fared.
+    pub fn record_backup(
+        &self,
+        started: DateTime<Local>,
+        finished: DateTime<Local>,
+        snapshot: &Path,
+        report: &BackupReport,
+    ) -> std::io::Result<()> {
+        self.record(
+            &HistoryEntry::timed(started, finished, HistoryKind::Backup, report.summary())
+                .with_stats(report.stats)
+                .with_snapshot(snapshot),
+        )?;
+        for target in &report.skipped {
+            self.record(
+                &HistoryEntry::new(
+                    started,
+                    HistoryKind::Skip,
+                    format!("target missing: {}", target),
+                )
+                .with_snapshot(snapshot),
+            )?;
+        }
+        for pruned in &report.pruned {
+            self.record(
+                &HistoryEntry::new(finished, HistoryKind::Prune, "removed by retention")
+                    .with_snapshot(pruned),
+            )?;
+        }
+        for mirror in &report.mirrors {
+            let entry = match &mirror.error {
+                None => HistoryEntry::new(
+                    finished,
+                    HistoryKind::Verify,
+                    format!("mirrored to {} and verified", mirror.path.display()),
+                ),
+                Some(e) => HistoryEntry::new(
+                    finished,
+                    HistoryKind::Error,
+                    format!("mirror to {} failed: {}", mirror.path.display(), e),
+                ),
+            };
+            self.record(&entry.with_snapshot(snapshot))?;
+            for pruned in &mirror.pruned {
+                self.record(
+                    &HistoryEntry::new(finished, HistoryKind::Prune, "removed by mirror retention")
+                        .with_snapshot(pruned),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads every entry, oldest first. Lines that fail to parse (e.g. a torn final write) are skipped.
+    pub fn load(&self) -> std::io::Result<Vec<HistoryEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(entry) = serde_json::from_str(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[test]
+pub fn test_history_round_trip() -> std::io::Result<()> {
+    let path = std::env::temp_dir().join("crucible-test-history.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let history = History::new(path.clone());
+    assert!(history.load()?.is_empty());
+    let revision = history.revision();
+
+    let mut backup = HistoryEntry::new(Local::now(), HistoryKind::Backup, "3 copied");
+    backup.files = 12;
+    backup.bytes = 4096;
+    backup.duration = Duration::from_millis(1500);
+    history.record(&backup)?;
+    OpenOptions::new()
+        .append(true)
+        .open(&path)?
+        .write_all(b"{\"timestamp\": \"torn\n")?;
+    let error = HistoryEntry::new(Local::now(), HistoryKind::Error, "disk full");
+    history.clone().record(&error)?;
+
+    assert_eq!(history.load()?, vec![backup, error]);
+    assert_eq!(history.revision(), revision + 2);
+    assert_eq!(HistoryKind::next_filter(None), Some(HistoryKind::Backup));
+    assert_eq!(
+        HistoryKind::next_filter(Some(HistoryKind::Backup)),
+        Some(HistoryKind::Skip)
+    );
+    assert_eq!(HistoryKind::next_filter(Some(HistoryKind::Error)), None);
+    std::fs::remove_file(&path)
+}
@@ -0,0 +1,175 @@
+use std::{
+    io::Read,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// How often a running hook is checked for having exited.
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Shell commands run around backups and restores.
+///
+/// Each one gets the details of the operation in `CRUCIBLE_*` environment variables. A pre-hook
+/// that exits non-zero cancels the operation; a failing post-hook is only reported.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Hooks {
+    pub pre_backup: Option<String>,
+    pub post_backup: Option<String>,
+    pub pre_restore: Option<String>,
+    pub post_restore: Option<String>,
+    /// How long a hook may run before it's killed and treated as failed.
+    pub timeout: Duration,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            pre_backup: None,
+            post_backup: None,
+            pre_restore: None,
+            post_restore: None,
+            timeout: Duration::from_secs(60 * 10),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("unable to run hook '{command}': {source}")]
+    Spawn {
+        command: String,
+        source: std::io::Error,
+    },
+    #[error("hook '{command}' failed ({status}){}", match output.is_empty() {
+        true => String::new(),
+        false => format!(": {}", output),
+    })]
+    Failed {
+        command: String,
+        status: String,
+        output: String,
+    },
+    #[error("hook '{command}' killed after running for {}s", timeout.as_secs_f64())]
+    TimedOut { command: String, timeout: Duration },
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    // Passed through as typed, since cmd doesn't follow the quoting `arg` would add.
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").raw_arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// Runs `command` through the platform shell with `env` added, killing it if it outlasts
+/// `timeout`. Its output is captured so it doesn't draw over the TUI.
+pub fn run_hook(command: &str, env: &[(&str, String)], timeout: Duration) -> Result<(), HookError> {
+    let spawn_error = |source| HookError::Spawn {
+        command: command.to_string(),
+        source,
+    };
+    let mut child = shell(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+    // Drained as it's written, so a hook with a lot to say can't stall on a full pipe.
+    let stderr = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = stderr.read_to_end(&mut output);
+            output
+        })
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(spawn_error)? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(HookError::TimedOut {
+                    command: command.to_string(),
+                    timeout,
+                });
+            }
+            None => std::thread::sleep(HOOK_POLL_INTERVAL),
+        }
+    };
+    match status.success() {
+        true => Ok(()),
+        false => Err(HookError::Failed {
+            command: command.to_string(),
+            status: status.to_string(),
+            output: match stderr.and_then(|reader| reader.join().ok()) {
+                Some(output) => String::from_utf8_lossy(&output).trim().to_string(),
+                None => String::new(),
+            },
+        }),
+    }
+}
+
+#[test]
+pub fn test_run_hook() {
+    let out = std::env::temp_dir().join("crucible-test-hook.txt");
+    let _ = std::fs::remove_file(&out);
+    let (write, fail, slow) = match cfg!(windows) {
+        true => (
+            format!(
+                "echo %CRUCIBLE_EVENT% %CRUCIBLE_RESULT%> \"{}\"",
+                out.display()
+            ),
+            "echo busy 1>&2 & exit /b 3",
+            "ping -n 30 127.0.0.1 > nul",
+        ),
+        false => (
+            format!(
+                "echo \"$CRUCIBLE_EVENT $CRUCIBLE_RESULT\" > '{}'",
+                out.display()
+            ),
+            "echo busy >&2; exit 3",
+            "sleep 30",
+        ),
+    };
+    let timeout = Duration::from_secs(60);
+    run_hook(
+        &write,
+        &[
+            ("CRUCIBLE_EVENT", String::from("backup")),
+            ("CRUCIBLE_RESULT", String::from("success")),
+        ],
+        timeout,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap().trim_end(),
+        "backup success"
+    );
+    std::fs::remove_file(&out).unwrap();
+
+    match run_hook(fail, &[], timeout) {
+        Err(HookError::Failed { output, .. }) => assert_eq!(output, "busy"),
+        _ => panic!("a failing hook should be reported"),
+    }
+
+    let started = Instant::now();
+    assert!(matches!(
+        run_hook(slow, &[], Duration::from_millis(200)),
+        Err(HookError::TimedOut { .. })
+    ));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
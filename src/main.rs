@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    fs::{read_dir, remove_dir_all},
+    fs::read_dir,
     io::stdout,
     path::PathBuf,
     sync::{
@@ -8,10 +8,13 @@ use std::{
         Arc, Mutex,
     },
     thread,
-    time::{Duration, SystemTime},
+    time::Duration,
 };
 
-use chrono::prelude::{DateTime, Local};
+use chrono::{
+    prelude::{DateTime, Local},
+    TimeDelta,
+};
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
@@ -25,15 +28,50 @@ use sysinfo::Disks;
 
 mod app;
 use app::{
-    back_up_files, duration_compare, get_backups_sorted, restore_backup, retrieve_minecraft_path,
-    Action, App, CodeResult, CurrentScreen, GeneralError,
+    check_passphrase, list_remote_backups, retrieve_minecraft_path, run_restore, run_verify,
+    Action, App, BackupError, BackupStatus, Clock, CodeResult, CurrentScreen, GeneralError,
+    Location, Pause, RestoreStatus, Scheduler, SystemClock, TargetPreview,
 };
 
+mod copy;
+mod crypto;
+use crypto::PASSPHRASE_VAR;
+
+mod filter;
+use filter::{preview, Preview};
+
+mod game;
+use game::{GameMonitor, GAME_POLL_INTERVAL};
+
+mod history;
+use history::{HistoryEntry, HistoryKind};
+
+mod hooks;
+
+mod rcon;
+
+mod s3;
+
+mod schedule;
+use schedule::Schedule;
+
+mod sftp;
+
+mod store;
+use store::LocalStore;
+
+mod watch;
+use watch::TargetWatcher;
+
+mod webhook;
+
 mod ui;
-use ui::{ui, UIState, BACKUPS_FREQ_CHARS, BACKUPS_MAX_CHARS};
+use ui::{ui, UIState, BACKUPS_FREQ_CHARS, BACKUPS_MAX_CHARS, BACKUPS_PAUSE_CHARS};
 
 // region: Constants
 
+const UI_REFRESH_MILLIS: i64 = 1000;
+
 // endregion Constants
 
 fn is_debounced(
@@ -65,69 +103,187 @@ fn run(
         let safe_app_copy = Arc::clone(&safe_app);
         let exit_flag = Arc::new(AtomicBool::new(false));
         let exit_flag_clone = Arc::clone(&exit_flag);
-        let timer_change = Arc::new(AtomicBool::new(false));
-        let timer_change_clone = Arc::clone(&timer_change);
         let manual_backup = Arc::new(AtomicBool::new(false));
         let manual_backup_clone = Arc::clone(&manual_backup);
-        let worker_error_flag = Arc::new(AtomicBool::new(false));
-        let worker_error_flag_clone = Arc::clone(&worker_error_flag);
+        let clock = SystemClock;
+        let history = safe_app.lock().unwrap().history.clone();
+        let progress = Arc::clone(&safe_app.lock().unwrap().progress);
+        let worker_history = history.clone();
 
         let worker = scope.spawn(move || {
-            let mut use_diff_time = false;
-            let mut diff_time = Duration::from_secs(0);
+            let mut scheduler = Scheduler::new(&clock);
+            let mut monitor = GameMonitor::new();
+            let mut game_was_running = false;
+            // Whether the current stretch without the game has already logged a skipped backup.
+            let mut skip_logged = false;
+            let mut watcher: Option<TargetWatcher> = None;
+            let mut watched: Vec<PathBuf> = Vec::new();
             while !exit_flag_clone.load(Ordering::Relaxed) {
-                let start = SystemTime::now();
-                let sleep_for;
-                if use_diff_time {
-                    sleep_for = diff_time;
-                    use_diff_time = false;
+                let config = safe_app_copy.lock().unwrap().configuration.clone();
+                if config.watch {
+                    let targets: Vec<PathBuf> =
+                        config.targets.iter().map(|t| mc_path.join(t)).collect();
+                    // Only retried when the targets change, so a failure is logged once.
+                    if targets != watched {
+                        watched = targets.clone();
+                        watcher = match TargetWatcher::new(&targets, thread::current()) {
+                            Ok(w) => Some(w),
+                            Err(e) => {
+                                let _ = worker_history.record(&HistoryEntry::new(
+                                    clock.now(),
+                                    HistoryKind::Error,
+                                    format!("unable to watch targets: {}", e),
+                                ));
+                                None
+                            }
+                        };
+                    }
                 } else {
-                    sleep_for = (*safe_app_copy.lock().unwrap()).configuration.frequency;
+                    watcher = None;
+                    watched.clear();
                 }
+                let next_run = scheduler.next_run(&config);
+                safe_app_copy.lock().unwrap().next_backup = next_run;
 
-                (*safe_app_copy.lock().unwrap()).next_backup =
-                    SystemTime::now().checked_add(sleep_for).unwrap().into();
-
-                thread::park_timeout(sleep_for);
-                let diff = SystemTime::now()
-                    .duration_since(start)
-                    .unwrap_or(Duration::from_secs(0));
-                if diff >= sleep_for {
-                    match back_up_files(&mc_path, &safe_app_copy.lock().unwrap().configuration) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("Error attempting to back up files.");
-                            println!("{}", e);
-                            worker_error_flag_clone.store(true, Ordering::Relaxed);
-                            return;
+                // While paused, sleep until the pause lifts rather than until the overdue backup.
+                let wait = match config.pause {
+                    Some(pause) if pause.is_active(clock.now()) => pause.remaining(clock.now()),
+                    _ => next_run.map(|next| {
+                        next.signed_duration_since(clock.now())
+                            .to_std()
+                            .unwrap_or(Duration::from_secs(0))
+                    }),
+                };
+                // Watch mode with nothing pending sleeps until a change, a settings edit or exit unparks it.
+                match (wait, config.game_aware) {
+                    (Some(wait), true) => thread::park_timeout(wait.min(GAME_POLL_INTERVAL)),
+                    (Some(wait), false) => thread::park_timeout(wait),
+                    (None, true) => thread::park_timeout(GAME_POLL_INTERVAL),
+                    (None, false) => thread::park(),
+                }
+                if exit_flag_clone.load(Ordering::Relaxed) {
+                    break;
+                }
+                let restore = safe_app_copy.lock().unwrap().pending_restore.take();
+                if let Some(restore) = restore {
+                    // Picks up anything changed while parked, such as a passphrase.
+                    let config = safe_app_copy.lock().unwrap().configuration.clone();
+                    let (location, name) = restore;
+                    let result = run_restore(
+                        &mc_path,
+                        &location,
+                        &name,
+                        &config,
+                        &progress,
+                        &clock,
+                        &worker_history,
+                    );
+                    let mut unwrapped_app = safe_app_copy.lock().unwrap();
+                    if let Err(e) = &result {
+                        // A half-restored world mustn't become the newest backup, so automatic
+                        // backups wait until the user resumes them.
+                        if !e.left_game_untouched() {
+                            unwrapped_app.configuration.pause = Some(Pause::Indefinitely);
+                            let _ = worker_history.record(&HistoryEntry::new(
+                                clock.now(),
+                                HistoryKind::Skip,
+                                "automatic backups paused after an incomplete restore",
+                            ));
+                            // Saved straight away so the pause outlives a quit or a crash.
+                            if let Err(e) = unwrapped_app.save_config() {
+                                let _ = worker_history.record(&HistoryEntry::new(
+                                    clock.now(),
+                                    HistoryKind::Error,
+                                    format!("unable to save the pause: {}", e),
+                                ));
+                            }
                         }
                     }
+                    // The history already has the details.
+                    unwrapped_app.last_restore = Some(match result {
+                        Ok(stats) => RestoreStatus::Succeeded(clock.now(), stats),
+                        Err(e) => RestoreStatus::Failed(clock.now(), e.to_string()),
+                    });
+                    drop(unwrapped_app);
+                    // The restore's own writes aren't changes worth backing up.
+                    if let Some(watcher) = &watcher {
+                        watcher.changed();
+                    }
+                    // Start over with the configuration as the restore left it.
+                    continue;
+                }
+                let verify = safe_app_copy.lock().unwrap().pending_verify.take();
+                if let Some(snapshot) = verify {
+                    let config = safe_app_copy.lock().unwrap().configuration.clone();
+                    // The outcome is in the history.
+                    let _ = run_verify(&snapshot, &config, &progress, &clock, &worker_history);
+                    continue;
+                }
+                if watcher.as_ref().is_some_and(|w| w.changed()) {
+                    scheduler.record_change(clock.now());
+                }
+
+                let paused = config
+                    .pause
+                    .is_some_and(|pause| pause.is_active(clock.now()));
+                let mut run_backup = false;
+                if config.game_aware {
+                    let running = monitor.is_running(&mc_path.join(&config.instance));
+                    safe_app_copy.lock().unwrap().game_running = Some(running);
+                    // One last backup once the session ends, to catch whatever was played since.
+                    run_backup = game_was_running && !running && !paused;
+                    game_was_running = running;
+                    if running {
+                        skip_logged = false;
+                    }
                 } else {
-                    if timer_change_clone.load(Ordering::Relaxed) {
-                        use_diff_time = true;
-                        diff_time = duration_compare(
-                            diff,
-                            (*safe_app_copy.lock().unwrap()).configuration.frequency,
-                            None,
-                        );
-                    } else if manual_backup_clone.load(Ordering::Relaxed) {
-                        manual_backup_clone.swap(false, Ordering::Relaxed);
-                        match back_up_files(&mc_path, &safe_app_copy.lock().unwrap().configuration)
-                        {
-                            Ok(_) => {}
-                            Err(e) => {
-                                println!("Error attempting to back up files.");
-                                println!("{}", e);
-                                worker_error_flag_clone.store(true, Ordering::Relaxed);
-                                return;
-                            }
+                    safe_app_copy.lock().unwrap().game_running = None;
+                    game_was_running = false;
+                }
+                if scheduler.is_due(&clock, &config) {
+                    scheduler.mark_run(clock.now());
+                    if config.game_aware && !game_was_running {
+                        if !skip_logged {
+                            skip_logged = true;
+                            let _ = worker_history.record(&HistoryEntry::new(
+                                clock.now(),
+                                HistoryKind::Skip,
+                                "scheduled backups skipped until the game is running",
+                            ));
                         }
-                        use_diff_time = true;
-                        diff_time = duration_compare(
-                            (*safe_app_copy.lock().unwrap()).configuration.frequency,
-                            diff,
-                            None,
-                        );
+                    } else {
+                        run_backup = true;
+                    }
+                }
+                if manual_backup_clone.swap(false, Ordering::Relaxed) {
+                    run_backup = true;
+                }
+
+                if run_backup {
+                    match app::run_backup(&mc_path, &config, &progress, &clock, &worker_history) {
+                        Ok((_, report)) => {
+                            scheduler.record_success();
+                            safe_app_copy.lock().unwrap().backup_status =
+                                BackupStatus::Succeeded(clock.now(), report);
+                        }
+                        // Not a failure, so it doesn't count towards retries or alerts.
+                        Err(BackupError::Cancelled) => {}
+                        Err(e) => {
+                            let at = clock.now();
+                            let attempts = scheduler.record_failure(at);
+                            safe_app_copy.lock().unwrap().backup_status = BackupStatus::Failed {
+                                at,
+                                error: e.to_string(),
+                                attempts,
+                                retry_at: scheduler.next_run(&config).unwrap_or(at),
+                            };
+                        }
+                    }
+                    // So the Backups screen shows the new snapshot and drops the pruned ones.
+                    safe_app_copy.lock().unwrap().refresh_local_backups();
+                    // Writes made while copying (including the RCON flush) are part of this backup.
+                    if let Some(watcher) = &watcher {
+                        watcher.changed();
                     }
                 }
             }
@@ -149,6 +305,16 @@ fn run(
 
         // Menu
         loop {
+            if state.quit_when_idle && safe_app.lock().unwrap().progress.get().is_none() {
+                retval = Ok(());
+                break;
+            }
+            {
+                let mut unwrapped_app = safe_app.lock().unwrap();
+                if unwrapped_app.current_screen == CurrentScreen::Backups {
+                    unwrapped_app.load_selected_report(state.backups.selected());
+                }
+            }
             // Draw
             match terminal.draw(|frame| {
                 ui(
@@ -168,10 +334,13 @@ fn run(
             }
             let start = Local::now();
             // Handle
+            // Redraw at least once a second so worker status changes show up without a key press.
             if match event::poll(std::time::Duration::from_millis(
-                (&safe_app.lock().unwrap().next_backup.timestamp_millis()
-                    - start.timestamp_millis()
-                    - 1) as u64,
+                match safe_app.lock().unwrap().next_backup {
+                    Some(next) => next.timestamp_millis() - start.timestamp_millis() - 1,
+                    None => UI_REFRESH_MILLIS,
+                }
+                .clamp(0, UI_REFRESH_MILLIS) as u64,
             )) {
                 Ok(v) => v,
                 Err(e) => {
@@ -189,7 +358,24 @@ fn run(
                     let now = Local::now();
                     if key.kind == KeyEventKind::Press {
                         let mut unwrapped_app = safe_app.lock().unwrap();
-                        if action == Action::ConfirmDelete
+                        if action == Action::ConfirmQuit {
+                            match key.code {
+                                KeyCode::Char('w') => {
+                                    state.quit_when_idle = true;
+                                    action = Action::None;
+                                }
+                                KeyCode::Char('c') => {
+                                    // The worker removes what was copied before it stops.
+                                    unwrapped_app.progress.cancel();
+                                    retval = Ok(());
+                                    break;
+                                }
+                                KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+                                    action = Action::None;
+                                }
+                                _ => {}
+                            }
+                        } else if action == Action::ConfirmDelete
                             || action == Action::ConfirmRestore
                             || action == Action::ConfirmNonExistent
                         {
@@ -202,17 +388,8 @@ fn run(
                                         Action::ConfirmDelete => {
                                             match &unwrapped_app.current_screen {
                                                 CurrentScreen::Backups => {
-                                                    match state.backups.selected() {
-                                                        Some(index) => {
-                                                            remove_dir_all(
-                                                                &get_backups_sorted(
-                                                                    &unwrapped_app.configuration,
-                                                                )
-                                                                .unwrap()[index]
-                                                                    .1,
-                                                            )?;
-                                                        }
-                                                        None => {}
+                                                    if let Some(index) = state.backups.selected() {
+                                                        unwrapped_app.delete_listed_backup(index);
                                                     }
                                                     Action::None
                                                 }
@@ -232,17 +409,17 @@ fn run(
                                                 _ => Action::None,
                                             }
                                         }
-                                        Action::ConfirmRestore => match state.backups.selected() {
-                                            Some(index) => {
-                                                restore_backup(
-                                                    &install_path,
-                                                    &get_backups_sorted(
-                                                        &unwrapped_app.configuration,
-                                                    )
-                                                    .unwrap()[index]
-                                                        .1,
-                                                    &unwrapped_app.configuration,
-                                                )?;
+                                        Action::ConfirmRestore => match state
+                                            .backups
+                                            .selected()
+                                            .and_then(|index| unwrapped_app.backups.get(index))
+                                        {
+                                            Some(entry) => {
+                                                // The worker runs it, so the UI keeps drawing
+                                                // its progress.
+                                                unwrapped_app.pending_restore = Some(entry.clone());
+                                                worker.thread().unpark();
+                                                unwrapped_app.set_view(CurrentScreen::Main);
                                                 Action::None
                                             }
                                             None => Action::None,
@@ -268,22 +445,88 @@ fn run(
                                     }
                                     match key.code {
                                         KeyCode::Char('q') => {
-                                            retval = Ok(());
-                                            break;
+                                            if unwrapped_app.progress.get().is_some() {
+                                                action = Action::ConfirmQuit;
+                                            } else {
+                                                retval = Ok(());
+                                                break;
+                                            }
                                         }
                                         KeyCode::Char('m') => {
                                             manual_backup.swap(true, Ordering::Relaxed);
                                             worker.thread().unpark();
                                         }
+                                        KeyCode::Char('c') => {
+                                            unwrapped_app.progress.cancel();
+                                        }
                                         KeyCode::Char('s') => {
                                             unwrapped_app.set_view(CurrentScreen::Settings);
                                         }
                                         KeyCode::Char('b') => {
+                                            unwrapped_app.refresh_local_backups();
+                                            unwrapped_app.backups_error = None;
+                                            // Remotes can take a while to answer, so they're
+                                            // listed away from the input thread.
+                                            if !unwrapped_app.remotes_loading {
+                                                unwrapped_app.remotes_loading = true;
+                                                let app = Arc::clone(&safe_app);
+                                                let config = unwrapped_app.configuration.clone();
+                                                let history = unwrapped_app.history.clone();
+                                                thread::spawn(move || {
+                                                    let remote =
+                                                        list_remote_backups(&config, &history);
+                                                    app.lock().unwrap().set_remote_backups(remote);
+                                                });
+                                            }
                                             unwrapped_app.set_view(CurrentScreen::Backups);
                                         }
+                                        KeyCode::Char('h') => {
+                                            state.history.select_first();
+                                            state.history_entries = None;
+                                            unwrapped_app.set_view(CurrentScreen::History);
+                                        }
+                                        KeyCode::Char('p') => {
+                                            match unwrapped_app.configuration.pause {
+                                                Some(pause) if pause.is_active(now) => {
+                                                    unwrapped_app.configuration.pause = None;
+                                                    conf_changed = true;
+                                                }
+                                                _ => {
+                                                    state.cursor = BACKUPS_PAUSE_CHARS - 1;
+                                                    state.num_buf = vec![
+                                                        String::from("0");
+                                                        BACKUPS_PAUSE_CHARS
+                                                    ];
+                                                    unwrapped_app.set_view(CurrentScreen::Pause);
+                                                }
+                                            }
+                                        }
                                         _ => {}
                                     }
                                 }
+                                CurrentScreen::History => match key.code {
+                                    KeyCode::Char('q') => {
+                                        unwrapped_app.set_view(CurrentScreen::Main);
+                                    }
+                                    KeyCode::Char('f') => {
+                                        state.history_filter =
+                                            HistoryKind::next_filter(state.history_filter);
+                                        state.history.select_first();
+                                    }
+                                    KeyCode::Down | KeyCode::Char('s') => {
+                                        state.history.select_next();
+                                    }
+                                    KeyCode::Up | KeyCode::Char('w') => {
+                                        state.history.select_previous();
+                                    }
+                                    KeyCode::Home => {
+                                        state.history.select_first();
+                                    }
+                                    KeyCode::End => {
+                                        state.history.select_last();
+                                    }
+                                    _ => {}
+                                },
                                 CurrentScreen::Settings => {
                                     action = Action::None;
                                     match key.code {
@@ -337,6 +580,53 @@ fn run(
                                         KeyCode::Char('t') => {
                                             unwrapped_app.set_view(CurrentScreen::Targets);
                                         }
+                                        KeyCode::Char('g') => {
+                                            unwrapped_app.configuration.game_aware =
+                                                !unwrapped_app.configuration.game_aware;
+                                            conf_changed = true;
+                                        }
+                                        KeyCode::Char('w') => {
+                                            unwrapped_app.configuration.watch =
+                                                !unwrapped_app.configuration.watch;
+                                            conf_changed = true;
+                                        }
+                                        KeyCode::Char('e') => {
+                                            match (
+                                                unwrapped_app.configuration.encrypt,
+                                                &unwrapped_app.configuration.passphrase,
+                                            ) {
+                                                (false, None) => {
+                                                    state.text_buf.clear();
+                                                    state.text_error = None;
+                                                    state.passphrase_first = None;
+                                                    unwrapped_app
+                                                        .set_view(CurrentScreen::Passphrase);
+                                                }
+                                                (encrypt, _) => {
+                                                    unwrapped_app.configuration.encrypt = !encrypt;
+                                                    conf_changed = true;
+                                                }
+                                            }
+                                        }
+                                        KeyCode::Char('r') => {
+                                            unwrapped_app.configuration.reflink =
+                                                unwrapped_app.configuration.reflink.next();
+                                            conf_changed = true;
+                                        }
+                                        KeyCode::Char('c') => {
+                                            unwrapped_app.configuration.consistent_worlds =
+                                                !unwrapped_app.configuration.consistent_worlds;
+                                            conf_changed = true;
+                                        }
+                                        KeyCode::Char('s') => {
+                                            state.text_buf =
+                                                match &unwrapped_app.configuration.schedule {
+                                                    Some(schedule) => schedule.to_string(),
+                                                    None => String::new(),
+                                                };
+                                            state.text_error = None;
+                                            unwrapped_app.set_view(CurrentScreen::Schedule);
+                                        }
                                         KeyCode::Char('p') => {
                                             unwrapped_app.set_view(CurrentScreen::Path);
                                             new_target = unwrapped_app.configuration.path.clone();
@@ -358,6 +648,22 @@ fn run(
                                     KeyCode::Char('d') => {
                                         action = Action::ConfirmDelete;
                                     }
+                                    KeyCode::Char('v') => {
+                                        if let Some((Location::Local, name)) = state
+                                            .backups
+                                            .selected()
+                                            .and_then(|index| unwrapped_app.backups.get(index))
+                                        {
+                                            // Handed to the worker like a restore, so the UI
+                                            // stays responsive and shows its progress.
+                                            unwrapped_app.pending_verify = Some(
+                                                LocalStore::new(&unwrapped_app.configuration.path)
+                                                    .path(name),
+                                            );
+                                            worker.thread().unpark();
+                                            unwrapped_app.set_view(CurrentScreen::Main);
+                                        }
+                                    }
                                     KeyCode::Down | KeyCode::Char('s') => {
                                         state.backups.select_next();
                                     }
@@ -580,6 +886,50 @@ fn run(
                                     KeyCode::Char('d') => {
                                         action = Action::ConfirmDelete;
                                     }
+                                    KeyCode::Char('v') => {
+                                        let config = unwrapped_app.configuration.clone();
+                                        if let Some(target) = state
+                                            .targets
+                                            .selected()
+                                            .and_then(|index| config.targets.get(index))
+                                            .cloned()
+                                        {
+                                            let patterns = config.patterns_for(&target);
+                                            unwrapped_app.target_preview = Some(TargetPreview {
+                                                target: target.clone(),
+                                                patterns: patterns.clone(),
+                                                result: None,
+                                            });
+                                            // Big targets take a while to walk, so it's done
+                                            // away from the input thread.
+                                            let app = Arc::clone(&safe_app);
+                                            let path = install_path.join(&target);
+                                            thread::spawn(move || {
+                                                let result = config
+                                                    .filter_for(&target, &path)
+                                                    .and_then(|filter| match filter {
+                                                        Some(filter) => preview(&path, &filter),
+                                                        None => Ok(Preview::default()),
+                                                    })
+                                                    .map_err(|e| e.to_string());
+                                                // Dropped if another preview was asked for, or
+                                                // the configuration changed, in the meantime.
+                                                if let Some(pending) = app
+                                                    .lock()
+                                                    .unwrap()
+                                                    .target_preview
+                                                    .as_mut()
+                                                    .filter(|pending| {
+                                                        pending.target == target
+                                                            && pending.patterns == patterns
+                                                            && pending.result.is_none()
+                                                    })
+                                                {
+                                                    pending.result = Some(result);
+                                                }
+                                            });
+                                        }
+                                    }
                                     KeyCode::Down | KeyCode::Char('s') => {
                                         state.targets.select_next();
                                     }
@@ -594,13 +944,108 @@ fn run(
                                     }
                                     _ => {}
                                 },
-                                CurrentScreen::Frequency | CurrentScreen::Max => {
+                                CurrentScreen::Schedule => match key.code {
+                                    KeyCode::Esc => {
+                                        unwrapped_app.set_view(CurrentScreen::Settings);
+                                    }
+                                    KeyCode::Char(c) => {
+                                        state.text_buf.push(c);
+                                    }
+                                    KeyCode::Backspace => {
+                                        state.text_buf.pop();
+                                    }
+                                    KeyCode::Enter => {
+                                        let parsed = match state.text_buf.trim() {
+                                            "" => Ok(None),
+                                            text => text.parse::<Schedule>().map(Some),
+                                        };
+                                        match parsed {
+                                            Ok(schedule) => {
+                                                unwrapped_app.configuration.schedule = schedule;
+                                                conf_changed = true;
+                                                unwrapped_app.set_view(CurrentScreen::Settings);
+                                            }
+                                            Err(e) => state.text_error = Some(e.to_string()),
+                                        }
+                                    }
+                                    _ => {}
+                                },
+                                CurrentScreen::Passphrase => match key.code {
+                                    KeyCode::Esc => {
+                                        state.text_buf.clear();
+                                        state.passphrase_first = None;
+                                        unwrapped_app.set_view(CurrentScreen::Main);
+                                    }
+                                    KeyCode::Char(c) => {
+                                        state.text_buf.push(c);
+                                    }
+                                    KeyCode::Backspace => {
+                                        state.text_buf.pop();
+                                    }
+                                    KeyCode::Enter => {
+                                        let entered = std::mem::take(&mut state.text_buf);
+                                        state.text_error = None;
+                                        let accepted = if entered.is_empty() {
+                                            state.text_error = Some(String::from(
+                                                "The passphrase can't be empty.",
+                                            ));
+                                            false
+                                        } else {
+                                            match check_passphrase(
+                                                &unwrapped_app.configuration,
+                                                &entered,
+                                            ) {
+                                                Ok(true) => true,
+                                                // Nothing to check it against, so a typo would
+                                                // only show at restore time; ask for it twice.
+                                                Ok(false) => match state.passphrase_first.take() {
+                                                    None => {
+                                                        state.passphrase_first =
+                                                            Some(entered.clone());
+                                                        false
+                                                    }
+                                                    Some(first) if first == entered => true,
+                                                    Some(_) => {
+                                                        state.text_error = Some(String::from(
+                                                            "The passphrases didn't match.",
+                                                        ));
+                                                        false
+                                                    }
+                                                },
+                                                Err(e) => {
+                                                    state.text_error = Some(e.to_string());
+                                                    false
+                                                }
+                                            }
+                                        };
+                                        if accepted {
+                                            // Entering a passphrase also turns encryption on.
+                                            unwrapped_app.configuration.passphrase = Some(entered);
+                                            if !unwrapped_app.configuration.encrypt {
+                                                unwrapped_app.configuration.encrypt = true;
+                                                conf_changed = true;
+                                            }
+                                            unwrapped_app.set_view(CurrentScreen::Main);
+                                        }
+                                    }
+                                    _ => {}
+                                },
+                                CurrentScreen::Frequency
+                                | CurrentScreen::Max
+                                | CurrentScreen::Pause => {
                                     let max: usize = match &unwrapped_app.current_screen {
                                         CurrentScreen::Frequency => BACKUPS_FREQ_CHARS,
                                         CurrentScreen::Max => BACKUPS_MAX_CHARS,
+                                        CurrentScreen::Pause => BACKUPS_PAUSE_CHARS,
                                         _ => 1,
                                     };
                                     match key.code {
+                                        KeyCode::Char('q')
+                                            if unwrapped_app.current_screen
+                                                == CurrentScreen::Pause =>
+                                        {
+                                            unwrapped_app.set_view(CurrentScreen::Main);
+                                        }
                                         KeyCode::Char('q') => {
                                             unwrapped_app.set_view(CurrentScreen::Settings);
                                         }
@@ -648,6 +1093,19 @@ fn run(
                                                 state.num_buf.remove(state.cursor + 1);
                                             }
                                         }
+                                        KeyCode::Enter
+                                            if unwrapped_app.current_screen
+                                                == CurrentScreen::Pause =>
+                                        {
+                                            let hours =
+                                                state.num_buf.join("").parse::<i64>().unwrap();
+                                            unwrapped_app.configuration.pause = Some(match hours {
+                                                0 => Pause::Indefinitely,
+                                                _ => Pause::Until(now + TimeDelta::hours(hours)),
+                                            });
+                                            conf_changed = true;
+                                            unwrapped_app.set_view(CurrentScreen::Main);
+                                        }
                                         KeyCode::Enter => {
                                             if unwrapped_app.current_screen == CurrentScreen::Max {
                                                 unwrapped_app.configuration.max_backups =
@@ -680,6 +1138,7 @@ fn run(
                         }
                         if conf_changed {
                             conf_changed = false;
+                            unwrapped_app.target_preview = None;
                             unwrapped_app.save_config()?;
                             worker.thread().unpark();
                         }
@@ -713,6 +1172,25 @@ fn run(
 fn main() -> CodeResult<()> {
     let mut app = App::new();
     app.load_config()?;
+    app.clean_partial_snapshots();
+    let mut passphrase_error = None;
+    app.configuration.passphrase = std::env::var(PASSPHRASE_VAR)
+        .ok()
+        .filter(|passphrase| !passphrase.is_empty())
+        .filter(
+            |passphrase| match check_passphrase(&app.configuration, passphrase) {
+                Ok(_) => true,
+                Err(e) => {
+                    passphrase_error = Some(format!("{} was ignored: {}", PASSPHRASE_VAR, e));
+                    false
+                }
+            },
+        );
+    if app.configuration.encrypt && app.configuration.passphrase.is_none()
+        || passphrase_error.is_some()
+    {
+        app.set_view(CurrentScreen::Passphrase);
+    }
 
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
@@ -721,6 +1199,7 @@ fn main() -> CodeResult<()> {
     terminal.clear()?;
 
     let mut state = UIState::new();
+    state.text_error = passphrase_error;
     state.backups.select_first();
     state.targets.select_first();
     state.target_change.select_first();
@@ -0,0 +1,228 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const RCON_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest packet the Minecraft server will send or accept.
+const MAX_PACKET: i32 = 4096 + 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RconConfig {
+    /// `host:port` of the server's RCON listener (`rcon.port` in server.properties).
+    pub address: String,
+    pub password: String,
+    /// Optional message announced in chat with `say` before each backup.
+    #[serde(default)]
+    pub broadcast: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("rcon connection error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("rcon address '{0}' did not resolve")]
+    Address(String),
+    #[error("rcon authentication failed")]
+    AuthFailed,
+    #[error("malformed rcon packet: {0}")]
+    Malformed(String),
+}
+
+pub type RconResult<T> = std::result::Result<T, RconError>;
+
+/// A logged-in connection speaking the Source RCON protocol that Minecraft servers implement.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    pub fn connect(address: &str, password: &str) -> RconResult<RconClient> {
+        let addr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| RconError::Address(address.to_string()))?;
+        let stream = TcpStream::connect_timeout(&addr, RCON_TIMEOUT)?;
+        stream.set_read_timeout(Some(RCON_TIMEOUT))?;
+        stream.set_write_timeout(Some(RCON_TIMEOUT))?;
+        let mut client = RconClient { stream, next_id: 1 };
+
+        let id = client.send(SERVERDATA_AUTH, password)?;
+        loop {
+            let (reply_id, kind, _) = client.receive()?;
+            if kind != SERVERDATA_AUTH_RESPONSE {
+                // Some servers send an empty response value ahead of the auth response.
+                continue;
+            }
+            return match reply_id == id {
+                true => Ok(client),
+                false => Err(RconError::AuthFailed),
+            };
+        }
+    }
+
+    /// Runs a console command and returns the server's reply.
+    pub fn command(&mut self, command: &str) -> RconResult<String> {
+        let id = self.send(SERVERDATA_EXECCOMMAND, command)?;
+        let (reply_id, _, body) = self.receive()?;
+        match reply_id == id {
+            true => Ok(body),
+            false => Err(RconError::Malformed(format!(
+                "expected reply to request {}, got {}",
+                id, reply_id
+            ))),
+        }
+    }
+
+    fn send(&mut self, kind: i32, body: &str) -> RconResult<i32> {
+        let id = self.next_id;
+        self.next_id += 1;
+        write_packet(&mut self.stream, id, kind, body)?;
+        Ok(id)
+    }
+
+    fn receive(&mut self) -> RconResult<(i32, i32, String)> {
+        read_packet(&mut self.stream)
+    }
+}
+
+pub fn write_packet(stream: &mut impl Write, id: i32, kind: i32, body: &str) -> RconResult<()> {
+    let length = (body.len() + 10) as i32;
+    if length > MAX_PACKET {
+        return Err(RconError::Malformed(format!(
+            "{} byte body is too long",
+            body.len()
+        )));
+    }
+    let mut packet = Vec::with_capacity(length as usize + 4);
+    packet.extend_from_slice(&length.to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&kind.to_le_bytes());
+    packet.extend_from_slice(body.as_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    stream.write_all(&packet)?;
+    Ok(())
+}
+
+pub fn read_packet(stream: &mut impl Read) -> RconResult<(i32, i32, String)> {
+    let mut word = [0u8; 4];
+    stream.read_exact(&mut word)?;
+    let length = i32::from_le_bytes(word);
+    if !(10..=MAX_PACKET).contains(&length) {
+        return Err(RconError::Malformed(format!("length {}", length)));
+    }
+    let mut rest = vec![0u8; length as usize];
+    stream.read_exact(&mut rest)?;
+    let id = i32::from_le_bytes(rest[0..4].try_into().unwrap());
+    let kind = i32::from_le_bytes(rest[4..8].try_into().unwrap());
+    let body = String::from_utf8_lossy(&rest[8..rest.len() - 2]).into_owned();
+    Ok((id, kind, body))
+}
+
+/// Holds world saving off on the server for the duration of a backup.
+///
+/// Saving is switched back on by `end`, or on drop if the backup bailed out early.
+pub struct SavePause {
+    client: Option<RconClient>,
+}
+
+impl SavePause {
+    pub fn begin(config: &RconConfig) -> RconResult<SavePause> {
+        let mut client = RconClient::connect(&config.address, &config.password)?;
+        if let Some(message) = &config.broadcast {
+            client.command(&format!("say {}", message))?;
+        }
+        client.command("save-off")?;
+        let mut pause = SavePause {
+            client: Some(client),
+        };
+        // From here on, dropping `pause` turns saving back on even if the flush fails.
+        pause.client.as_mut().unwrap().command("save-all flush")?;
+        Ok(pause)
+    }
+
+    pub fn end(mut self) -> RconResult<()> {
+        match self.client.take() {
+            Some(mut client) => client.command("save-on").map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SavePause {
+    fn drop(&mut self) {
+        if let Some(mut client) = self.client.take() {
+            let _ = client.command("save-on");
+        }
+    }
+}
+
+/// Starts a single-connection RCON stand-in that records every command it is sent.
+#[cfg(test)]
+pub fn mock_rcon_server(password: &'static str) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut commands = Vec::new();
+        let (id, _, body) = read_packet(&mut stream).unwrap();
+        if body != password {
+            write_packet(&mut stream, -1, SERVERDATA_AUTH_RESPONSE, "").unwrap();
+            return commands;
+        }
+        write_packet(&mut stream, id, 0, "").unwrap();
+        write_packet(&mut stream, id, SERVERDATA_AUTH_RESPONSE, "").unwrap();
+        while let Ok((id, _, body)) = read_packet(&mut stream) {
+            write_packet(&mut stream, id, 0, &format!("ran {}", body)).unwrap();
+            commands.push(body);
+        }
+        commands
+    });
+    (address, handle)
+}
+
+#[test]
+pub fn test_save_pause() {
+    let (address, server) = mock_rcon_server("hunter2");
+    let config = RconConfig {
+        address,
+        password: String::from("hunter2"),
+        broadcast: Some(String::from("Backing up the world")),
+    };
+    let pause = SavePause::begin(&config).unwrap();
+    pause.end().unwrap();
+    assert_eq!(
+        server.join().unwrap(),
+        vec![
+            "say Backing up the world",
+            "save-off",
+            "save-all flush",
+            "save-on"
+        ]
+    );
+
+    let (address, server) = mock_rcon_server("hunter2");
+    let mut client = RconClient::connect(&address, "hunter2").unwrap();
+    assert_eq!(client.command("list").unwrap(), "ran list");
+    drop(SavePause {
+        client: Some(client),
+    });
+    assert_eq!(server.join().unwrap(), vec!["list", "save-on"]);
+
+    let (address, server) = mock_rcon_server("hunter2");
+    assert!(matches!(
+        RconClient::connect(&address, "wrong"),
+        Err(RconError::AuthFailed)
+    ));
+    assert!(server.join().unwrap().is_empty());
+}
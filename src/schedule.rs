@@ -0,0 +1,238 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::{
+    prelude::{DateTime, Local},
+    NaiveTime, TimeDelta, TimeZone,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// When automatic backups run, in place of the fixed `frequency`.
+///
+/// Written the same way in the config file and the Settings screen:
+/// `daily 04:00`, `every 10m 18:00-02:00` or `cron */10 18-23 * * *`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub enum Schedule {
+    Daily {
+        at: NaiveTime,
+    },
+    /// Every `interval`, counted from the start of the window. Without a window the day starts at midnight.
+    Every {
+        interval: Duration,
+        window: Option<(NaiveTime, NaiveTime)>,
+    },
+    Cron {
+        expression: String,
+        schedule: Box<cron::Schedule>,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("unknown schedule '{0}', expected 'daily', 'every' or 'cron'")]
+    Unknown(String),
+    #[error("invalid time '{0}', expected HH:MM")]
+    Time(String),
+    #[error("invalid interval '{0}', expected e.g. 90s, 10m or 2h")]
+    Interval(String),
+    #[error("invalid cron expression: {0}")]
+    Cron(String),
+}
+
+impl Schedule {
+    /// The first run strictly after `after`, or `None` if the schedule never fires again.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Daily { at } => next_in_window(after, TimeDelta::days(1), *at, *at),
+            Schedule::Every { interval, window } => {
+                let (from, until) = window.unwrap_or((NaiveTime::MIN, NaiveTime::MIN));
+                next_in_window(after, TimeDelta::from_std(*interval).ok()?, from, until)
+            }
+            Schedule::Cron { schedule, .. } => schedule.after(&after).next(),
+        }
+    }
+}
+
+/// Steps through the window `from..until` (wrapping past midnight if `until <= from`) every `interval`.
+fn next_in_window(
+    after: DateTime<Local>,
+    interval: TimeDelta,
+    from: NaiveTime,
+    until: NaiveTime,
+) -> Option<DateTime<Local>> {
+    let length = match until.signed_duration_since(from) {
+        d if d > TimeDelta::zero() => d,
+        d => d + TimeDelta::days(1),
+    };
+    let after_local = after.naive_local();
+    // Yesterday's window may still be open if it wraps past midnight.
+    for offset in -1..=1 {
+        let start = (after_local.date() + TimeDelta::days(offset)).and_time(from);
+        let end = start + length;
+        if after_local >= end {
+            continue;
+        }
+        let candidate = match after_local < start {
+            true => start,
+            false => {
+                let steps = (after_local - start).num_seconds() / interval.num_seconds() + 1;
+                start + TimeDelta::seconds(steps * interval.num_seconds())
+            }
+        };
+        if candidate < end {
+            // Times skipped by a daylight saving jump have no local equivalent; move on to the next window.
+            if let Some(next) = Local.from_local_datetime(&candidate).earliest() {
+                return Some(next);
+            }
+        }
+    }
+    None
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, ScheduleError> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| ScheduleError::Time(s.to_string()))
+}
+
+fn parse_interval(s: &str) -> Result<Duration, ScheduleError> {
+    let error = || ScheduleError::Interval(s.to_string());
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+    let count = s[..split].parse::<u64>().map_err(|_| error())?;
+    let unit = match &s[split..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(error()),
+    };
+    match count {
+        0 => Err(error()),
+        _ => Ok(Duration::from_secs(count * unit)),
+    }
+}
+
+fn format_interval(interval: Duration) -> String {
+    let secs = interval.as_secs();
+    match secs {
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (keyword, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        match keyword.to_lowercase().as_str() {
+            "daily" => Ok(Schedule::Daily {
+                at: parse_time(rest)?,
+            }),
+            "every" => {
+                let (interval, window) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let window = match window.trim() {
+                    "" => None,
+                    window => {
+                        let (from, until) = window
+                            .split_once('-')
+                            .ok_or_else(|| ScheduleError::Time(window.to_string()))?;
+                        Some((parse_time(from.trim())?, parse_time(until.trim())?))
+                    }
+                };
+                Ok(Schedule::Every {
+                    interval: parse_interval(interval)?,
+                    window,
+                })
+            }
+            "cron" => {
+                // The cron crate wants a seconds field; accept the usual five-field form too.
+                let full = match rest.split_whitespace().count() {
+                    5 => format!("0 {}", rest),
+                    _ => rest.to_string(),
+                };
+                Ok(Schedule::Cron {
+                    expression: rest.to_string(),
+                    schedule: Box::new(
+                        cron::Schedule::from_str(&full)
+                            .map_err(|e| ScheduleError::Cron(e.to_string()))?,
+                    ),
+                })
+            }
+            _ => Err(ScheduleError::Unknown(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Schedule::Daily { at } => write!(f, "daily {}", at.format("%H:%M")),
+            Schedule::Every { interval, window } => {
+                write!(f, "every {}", format_interval(*interval))?;
+                match window {
+                    Some((from, until)) => {
+                        write!(f, " {}-{}", from.format("%H:%M"), until.format("%H:%M"))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Schedule::Cron { expression, .. } => write!(f, "cron {}", expression),
+        }
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = ScheduleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(value: Schedule) -> Self {
+        value.to_string()
+    }
+}
+
+#[test]
+pub fn test_schedule_next_after() {
+    let at = |d, h, m| Local.with_ymd_and_hms(2024, 6, d, h, m, 0).unwrap();
+
+    let daily: Schedule = "daily 04:00".parse().unwrap();
+    assert_eq!(daily.next_after(at(1, 3, 0)), Some(at(1, 4, 0)));
+    assert_eq!(daily.next_after(at(1, 4, 0)), Some(at(2, 4, 0)));
+
+    let evening: Schedule = "every 10m 18:00-02:00".parse().unwrap();
+    assert_eq!(evening.next_after(at(1, 12, 0)), Some(at(1, 18, 0)));
+    assert_eq!(evening.next_after(at(1, 18, 0)), Some(at(1, 18, 10)));
+    assert_eq!(evening.next_after(at(1, 23, 55)), Some(at(2, 0, 0)));
+    assert_eq!(evening.next_after(at(2, 1, 45)), Some(at(2, 1, 50)));
+    assert_eq!(evening.next_after(at(2, 1, 50)), Some(at(2, 18, 0)));
+
+    let hourly: Schedule = "every 1h".parse().unwrap();
+    assert_eq!(hourly.next_after(at(1, 23, 30)), Some(at(2, 0, 0)));
+
+    let cron: Schedule = "cron 30 4 * * *".parse().unwrap();
+    assert_eq!(cron.next_after(at(1, 5, 0)), Some(at(2, 4, 30)));
+
+    for text in [
+        "daily 04:00",
+        "every 10m 18:00-02:00",
+        "every 90s",
+        "cron 30 4 * * *",
+    ] {
+        assert_eq!(text.parse::<Schedule>().unwrap().to_string(), text);
+    }
+    for text in [
+        "weekly 04:00",
+        "daily 4pm",
+        "every 0m",
+        "every 10x",
+        "cron 61 * * * *",
+    ] {
+        assert!(text.parse::<Schedule>().is_err(), "{}", text);
+    }
+}
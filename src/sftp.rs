@@ -0,0 +1,832 @@
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use ssh2::{CheckResult, FileStat, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+use thiserror::Error;
+
+use crate::{
+    app::{BackupError, BackupResult, CopyStats},
+    store::{
+        is_safe_component, is_snapshot_name, parse_snapshot_name, sort_snapshot_names, BackupStore,
+        SnapshotStat, PARTIAL_SUFFIX,
+    },
+};
+
+const SFTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn default_port() -> u16 {
+    22
+}
+
+fn default_max_backups() -> u8 {
+    10
+}
+
+/// A folder on an SSH server that snapshots are pushed to.
+///
+/// The server's key must already be in `~/.ssh/known_hosts`. Logs in with `key_file` if set (with
+/// `password` as its passphrase), otherwise with `password`, otherwise through the SSH agent.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub user: String,
+    /// Remote folder the snapshots go in. Relative paths start at the user's home.
+    pub path: String,
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Remote snapshots kept by retention, independently of the local `max_backups`.
+    #[serde(default = "default_max_backups")]
+    pub max_backups: u8,
+}
+
+impl SftpConfig {
+    /// `sftp://user@host:port/path`, for reports and history.
+    pub fn display(&self) -> String {
+        format!(
+            "sftp://{}@{}:{}/{}",
+            self.user,
+            self.host,
+            self.port,
+            self.path.trim_start_matches('/')
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SftpError {
+    #[error("unable to connect to '{host}': {source}")]
+    Connect {
+        host: String,
+        source: std::io::Error,
+    },
+    #[error("ssh session failed: {0}")]
+    Session(#[from] ssh2::Error),
+    #[error("host key of '{0}' is not in known_hosts, connect with ssh once to add it")]
+    UnknownHost(String),
+    #[error("host key of '{0}' does not match known_hosts")]
+    HostKeyMismatch(String),
+    #[error("sftp '{}' failed: {source}", path.display())]
+    Remote { path: PathBuf, source: ssh2::Error },
+    #[error("unable to transfer '{}': {source}", path.display())]
+    Transfer {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("unable to access '{}': {source}", path.display())]
+    Local {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("uploaded file '{}' is missing or has the wrong size", .0.display())]
+    Mismatch(PathBuf),
+    #[error("remote file '{}' would be written outside the snapshot", .0.display())]
+    UnsafeName(PathBuf),
+}
+
+pub type SftpResult<T> = std::result::Result<T, SftpError>;
+
+/// A remote file being written, which an interrupted upload seeks into to carry on.
+trait RemoteWriter: Write + Seek {}
+
+impl<T: Write + Seek> RemoteWriter for T {}
+
+/// The SFTP calls the client makes, so uploads, listing and retention can run against an
+/// in-memory server in tests.
+trait RemoteFs {
+    fn stat(&self, path: &Path) -> Result<FileStat, ssh2::Error>;
+    fn mkdir(&self, path: &Path) -> Result<(), ssh2::Error>;
+    fn readdir(&self, path: &Path) -> Result<Vec<(PathBuf, FileStat)>, ssh2::Error>;
+    fn unlink(&self, path: &Path) -> Result<(), ssh2::Error>;
+    fn rmdir(&self, path: &Path) -> Result<(), ssh2::Error>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), ssh2::Error>;
+    /// Opens `path` for writing, creating it, and emptying it first if `truncate`.
+    fn open_write(&self, path: &Path, truncate: bool)
+        -> Result<Box<dyn RemoteWriter>, ssh2::Error>;
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>, ssh2::Error>;
+}
+
+impl RemoteFs for Sftp {
+    fn stat(&self, path: &Path) -> Result<FileStat, ssh2::Error> {
+        Sftp::stat(self, path)
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<(), ssh2::Error> {
+        Sftp::mkdir(self, path, 0o755)
+    }
+
+    fn readdir(&self, path: &Path) -> Result<Vec<(PathBuf, FileStat)>, ssh2::Error> {
+        Sftp::readdir(self, path)
+    }
+
+    fn unlink(&self, path: &Path) -> Result<(), ssh2::Error> {
+        Sftp::unlink(self, path)
+    }
+
+    fn rmdir(&self, path: &Path) -> Result<(), ssh2::Error> {
+        Sftp::rmdir(self, path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), ssh2::Error> {
+        Sftp::rename(self, from, to, None)
+    }
+
+    fn open_write(
+        &self,
+        path: &Path,
+        truncate: bool,
+    ) -> Result<Box<dyn RemoteWriter>, ssh2::Error> {
+        let flags = match truncate {
+            true => OpenFlags::WRITE | OpenFlags::TRUNCATE,
+            false => OpenFlags::WRITE | OpenFlags::CREATE,
+        };
+        Ok(Box::new(self.open_mode(
+            path,
+            flags,
+            0o644,
+            OpenType::File,
+        )?))
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>, ssh2::Error> {
+        Ok(Box::new(self.open(path)?))
+    }
+}
+
+pub struct SftpClient {
+    config: SftpConfig,
+    // Keeps the connection open for `fs`.
+    _session: Option<Session>,
+    fs: Box<dyn RemoteFs>,
+}
+
+impl SftpClient {
+    pub fn connect(config: SftpConfig) -> SftpResult<SftpClient> {
+        let connect_error = |source| SftpError::Connect {
+            host: config.host.clone(),
+            source,
+        };
+        let address = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .map_err(connect_error)?
+            .next()
+            .ok_or_else(|| connect_error(std::io::ErrorKind::NotFound.into()))?;
+        let stream = TcpStream::connect_timeout(&address, SFTP_TIMEOUT).map_err(connect_error)?;
+
+        let mut session = Session::new()?;
+        session.set_timeout(SFTP_TIMEOUT.as_millis() as u32);
+        session.set_tcp_stream(stream);
+        session.handshake()?;
+        verify_host_key(&session, &config)?;
+        match (&config.key_file, &config.password) {
+            (Some(key), passphrase) => {
+                session.userauth_pubkey_file(&config.user, None, key, passphrase.as_deref())?
+            }
+            (None, Some(password)) => session.userauth_password(&config.user, password)?,
+            (None, None) => session.userauth_agent(&config.user)?,
+        }
+        let sftp = session.sftp()?;
+        Ok(SftpClient {
+            config,
+            _session: Some(session),
+            fs: Box::new(sftp),
+        })
+    }
+
+    fn root(&self) -> PathBuf {
+        PathBuf::from(&self.config.path)
+    }
+
+    fn stat(&self, path: &Path) -> Option<FileStat> {
+        self.fs.stat(path).ok()
+    }
+
+    fn mkdir_all(&self, path: &Path) -> SftpResult<()> {
+        if path.as_os_str().is_empty() || self.stat(path).is_some_and(|stat| stat.is_dir()) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.mkdir_all(parent)?;
+        }
+        self.fs.mkdir(path).map_err(|source| SftpError::Remote {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Every file under `dir`, as paths relative to it, with their sizes.
+    fn remote_files(
+        &self,
+        dir: &Path,
+        relative: &Path,
+        files: &mut Vec<(PathBuf, u64)>,
+    ) -> SftpResult<()> {
+        let path = dir.join(relative);
+        let entries = self
+            .fs
+            .readdir(&path)
+            .map_err(|source| SftpError::Remote { path, source })?;
+        for (entry, stat) in entries {
+            let name = match entry.file_name().and_then(|name| name.to_str()) {
+                Some(name) if is_safe_component(name) => relative.join(name),
+                _ => return Err(SftpError::UnsafeName(entry)),
+            };
+            if stat.is_dir() {
+                self.remote_files(dir, &name, files)?;
+            } else {
+                files.push((name, stat.size.unwrap_or(0)));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_all(&self, path: &Path) -> SftpResult<()> {
+        let remote_error = |source| SftpError::Remote {
+            path: path.to_path_buf(),
+            source,
+        };
+        for (entry, stat) in self.fs.readdir(path).map_err(remote_error)? {
+            match stat.is_dir() {
+                true => self.remove_all(&entry)?,
+                false => self.fs.unlink(&entry).map_err(|source| SftpError::Remote {
+                    path: entry.clone(),
+                    source,
+                })?,
+            }
+        }
+        self.fs.rmdir(path).map_err(remote_error)
+    }
+}
+
+fn verify_host_key(session: &Session, config: &SftpConfig) -> SftpResult<()> {
+    let mut known_hosts = session.known_hosts()?;
+    if let Some(file) = dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts")) {
+        // A missing file just means no host is known yet.
+        let _ = known_hosts.read_file(&file, KnownHostFileKind::OpenSSH);
+    }
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| SftpError::UnknownHost(config.host.clone()))?;
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(SftpError::HostKeyMismatch(config.host.clone())),
+        CheckResult::NotFound | CheckResult::Failure => {
+            Err(SftpError::UnknownHost(config.host.clone()))
+        }
+    }
+}
+
+/// Where to pick up a file whose remote copy is `remote` bytes long, or `None` if it is complete.
+fn resume_offset(local: u64, remote: Option<u64>) -> Option<u64> {
+    match remote {
+        Some(remote) if remote == local => None,
+        Some(remote) if remote < local => Some(remote),
+        _ => Some(0),
+    }
+}
+
+fn local_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> SftpResult<()> {
+    let path = dir.join(relative);
+    let local_error = |source| SftpError::Local {
+        path: path.clone(),
+        source,
+    };
+    for entry in std::fs::read_dir(&path).map_err(local_error)? {
+        let entry = entry.map_err(local_error)?;
+        let name = relative.join(entry.file_name());
+        if entry.file_type().map_err(local_error)?.is_dir() {
+            local_files(dir, &name, files)?;
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Uploads `snapshot` as `name` into a `.partial` folder and renames it once every file is there.
+///
+/// Files already in the `.partial` folder from an interrupted upload are continued from where
+/// they stopped instead of being sent again.
+pub fn upload_snapshot(client: &SftpClient, name: &str, snapshot: &Path) -> SftpResult<CopyStats> {
+    let staging = client.root().join(format!("{}{}", name, PARTIAL_SUFFIX));
+    let mut files = Vec::new();
+    local_files(snapshot, Path::new(""), &mut files)?;
+    let mut stats = CopyStats::default();
+    for file in files {
+        let local = snapshot.join(&file);
+        let remote = staging.join(&file);
+        let local_error = |source| SftpError::Local {
+            path: local.clone(),
+            source,
+        };
+        let length = std::fs::metadata(&local).map_err(local_error)?.len();
+        stats.files += 1;
+        stats.bytes += length;
+        let offset = match resume_offset(length, client.stat(&remote).and_then(|s| s.size)) {
+            Some(offset) => offset,
+            None => continue,
+        };
+        client.mkdir_all(remote.parent().unwrap_or(&staging))?;
+        let transfer_error = |source| SftpError::Transfer {
+            path: remote.clone(),
+            source,
+        };
+        let mut writer = client
+            .fs
+            .open_write(&remote, offset == 0)
+            .map_err(|source| SftpError::Remote {
+                path: remote.clone(),
+                source,
+            })?;
+        writer
+            .seek(SeekFrom::Start(offset))
+            .map_err(transfer_error)?;
+        let mut reader = std::fs::File::open(&local).map_err(local_error)?;
+        reader.seek(SeekFrom::Start(offset)).map_err(local_error)?;
+        std::io::copy(&mut reader, &mut writer).map_err(transfer_error)?;
+    }
+
+    let mut uploaded = Vec::new();
+    client.remote_files(&staging, Path::new(""), &mut uploaded)?;
+    let mut expected = Vec::new();
+    local_files(snapshot, Path::new(""), &mut expected)?;
+    for file in expected {
+        let length = std::fs::metadata(snapshot.join(&file))
+            .map(|m| m.len())
+            .ok();
+        if !uploaded
+            .iter()
+            .any(|(f, size)| *f == file && Some(*size) == length)
+        {
+            return Err(SftpError::Mismatch(staging.join(file)));
+        }
+    }
+    let target = client.root().join(name);
+    client
+        .fs
+        .rename(&staging, &target)
+        .map_err(|source| SftpError::Remote {
+            path: target,
+            source,
+        })?;
+    Ok(stats)
+}
+
+/// Finishes uploads left in `.partial` folders whose snapshot is still in `local_dir`, and removes
+/// the ones whose snapshot is gone. Returns the names that were finished.
+pub fn resume_uploads(client: &SftpClient, local_dir: &Path) -> SftpResult<Vec<String>> {
+    let root = client.root();
+    client.mkdir_all(&root)?;
+    let entries = client
+        .fs
+        .readdir(&root)
+        .map_err(|source| SftpError::Remote {
+            path: root.clone(),
+            source,
+        })?;
+    let mut resumed = Vec::new();
+    for (entry, _) in entries {
+        let file_name = entry.file_name().unwrap_or_default().to_string_lossy();
+        if let Some(name) = file_name.strip_suffix(PARTIAL_SUFFIX) {
+            let snapshot = local_dir.join(name);
+            match snapshot.is_dir() {
+                true => {
+                    upload_snapshot(client, name, &snapshot)?;
+                    resumed.push(name.to_string());
+                }
+                false => client.remove_all(&entry)?,
+            }
+        }
+    }
+    Ok(resumed)
+}
+
+/// Names of the finished snapshots on the server, oldest first.
+pub fn list_snapshots(client: &SftpClient) -> SftpResult<Vec<String>> {
+    let root = client.root();
+    let entries = match client.stat(&root) {
+        Some(_) => client
+            .fs
+            .readdir(&root)
+            .map_err(|source| SftpError::Remote { path: root, source })?,
+        None => Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .iter()
+        .filter(|(_, stat)| stat.is_dir())
+        .filter_map(|(entry, _)| entry.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| is_snapshot_name(name))
+        .collect();
+    sort_snapshot_names(&mut names);
+    Ok(names)
+}
+
+pub fn download_snapshot(client: &SftpClient, name: &str, dst: &Path) -> SftpResult<CopyStats> {
+    let source = client.root().join(name);
+    let mut files = Vec::new();
+    client.remote_files(&source, Path::new(""), &mut files)?;
+    let mut stats = CopyStats::default();
+    for (file, _) in files {
+        let remote = source.join(&file);
+        let local = dst.join(&file);
+        if let Some(parent) = local.parent() {
+            std::fs::create_dir_all(parent).map_err(|source| SftpError::Local {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        let mut reader = client
+            .fs
+            .open_read(&remote)
+            .map_err(|source| SftpError::Remote {
+                path: remote.clone(),
+                source,
+            })?;
+        let mut writer = std::fs::File::create(&local).map_err(|source| SftpError::Local {
+            path: local,
+            source,
+        })?;
+        stats.bytes +=
+            std::io::copy(&mut reader, &mut writer).map_err(|source| SftpError::Transfer {
+                path: remote,
+                source,
+            })?;
+        stats.files += 1;
+    }
+    Ok(stats)
+}
+
+pub fn delete_snapshot(client: &SftpClient, name: &str) -> SftpResult<()> {
+    client.remove_all(&client.root().join(name))
+}
+
+impl BackupStore for SftpClient {
+    fn list(&self) -> BackupResult<Vec<String>> {
+        Ok(list_snapshots(self)?)
+    }
+
+    /// Finishes any interrupted uploads from `src`'s folder before uploading `src` itself.
+    fn write_snapshot(&self, name: &str, src: &Path) -> BackupResult<CopyStats> {
+        if let Some(local_dir) = src.parent() {
+            resume_uploads(self, local_dir)?;
+        }
+        Ok(upload_snapshot(self, name, src)?)
+    }
+
+    fn read_snapshot(&self, name: &str, dst: &Path) -> BackupResult<CopyStats> {
+        Ok(download_snapshot(self, name, dst)?)
+    }
+
+    fn delete(&self, name: &str) -> BackupResult<()> {
+        Ok(delete_snapshot(self, name)?)
+    }
+
+    fn stat(&self, name: &str) -> BackupResult<SnapshotStat> {
+        let path = self.root().join(name);
+        if self.stat(&path).is_none() {
+            return Err(BackupError::MissingSnapshot(name.to_string()));
+        }
+        let mut files = Vec::new();
+        self.remote_files(&path, Path::new(""), &mut files)?;
+        Ok(SnapshotStat {
+            taken: parse_snapshot_name(name),
+            files: files.len() as u64,
+            bytes: files.iter().map(|(_, size)| size).sum(),
+        })
+    }
+}
+
+#[test]
+pub fn test_sftp_resume_offset() {
+    assert_eq!(resume_offset(100, None), Some(0));
+    assert_eq!(resume_offset(100, Some(40)), Some(40));
+    assert_eq!(resume_offset(100, Some(100)), None);
+    // A remote file longer than ours isn't a prefix of it.
+    assert_eq!(resume_offset(100, Some(140)), Some(0));
+}
+
+#[test]
+pub fn test_sftp_connect_errors() {
+    use std::{io::Write, net::TcpListener};
+
+    let config = |port| SftpConfig {
+        host: String::from("127.0.0.1"),
+        port,
+        user: String::from("minecraft"),
+        path: String::from("/srv/backups"),
+        key_file: None,
+        password: None,
+        max_backups: default_max_backups(),
+    };
+    assert_eq!(
+        config(2222).display(),
+        "sftp://minecraft@127.0.0.1:2222/srv/backups"
+    );
+
+    // Something that isn't an SSH server fails the handshake.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+    });
+    assert!(matches!(
+        SftpClient::connect(config(port)),
+        Err(SftpError::Session(_))
+    ));
+    server.join().unwrap();
+
+    // Nothing listening on the port at all.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    assert!(matches!(
+        SftpClient::connect(config(port)),
+        Err(SftpError::Connect { .. })
+    ));
+}
+
+/// Files and folders held in memory, standing in for an SFTP server in tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct MemoryFs {
+    /// Contents by path, `None` for a folder.
+    entries: std::sync::Arc<std::sync::Mutex<std::collections::BTreeMap<PathBuf, Option<Vec<u8>>>>>,
+    /// Bytes written so far, to tell a resumed upload from one sent again in full.
+    written: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    /// Set to have writes acknowledged but lost, like a server with a full disk.
+    lossy: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(test)]
+impl MemoryFs {
+    fn missing() -> ssh2::Error {
+        ssh2::Error::from_errno(ssh2::ErrorCode::SFTP(2))
+    }
+
+    fn file_stat(contents: &Option<Vec<u8>>) -> FileStat {
+        FileStat {
+            size: Some(contents.as_ref().map_or(0, |data| data.len() as u64)),
+            uid: None,
+            gid: None,
+            perm: Some(match contents {
+                Some(_) => 0o100644,
+                None => 0o040755,
+            }),
+            atime: None,
+            mtime: None,
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), Some(data.to_vec()));
+    }
+
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap().get(path).cloned().flatten()
+    }
+}
+
+/// A file open for writing on a `MemoryFs`, saved back after every write.
+#[cfg(test)]
+struct MemoryFile {
+    fs: MemoryFs,
+    path: PathBuf,
+    data: std::io::Cursor<Vec<u8>>,
+}
+
+#[cfg(test)]
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        use std::sync::atomic::Ordering;
+
+        self.fs.written.fetch_add(buf.len(), Ordering::Relaxed);
+        if !self.fs.lossy.load(Ordering::Relaxed) {
+            self.data.write_all(buf)?;
+            self.fs.write(&self.path, self.data.get_ref());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.data.seek(pos)
+    }
+}
+
+#[cfg(test)]
+impl RemoteFs for MemoryFs {
+    fn stat(&self, path: &Path) -> Result<FileStat, ssh2::Error> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(contents) => Ok(MemoryFs::file_stat(contents)),
+            None => Err(MemoryFs::missing()),
+        }
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<(), ssh2::Error> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), None);
+        Ok(())
+    }
+
+    fn readdir(&self, path: &Path) -> Result<Vec<(PathBuf, FileStat)>, ssh2::Error> {
+        let entries = self.entries.lock().unwrap();
+        if entries.get(path) != Some(&None) {
+            return Err(MemoryFs::missing());
+        }
+        Ok(entries
+            .iter()
+            .filter(|(entry, _)| entry.parent() == Some(path))
+            .map(|(entry, contents)| (entry.clone(), MemoryFs::file_stat(contents)))
+            .collect())
+    }
+
+    fn unlink(&self, path: &Path) -> Result<(), ssh2::Error> {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(MemoryFs::missing)
+    }
+
+    fn rmdir(&self, path: &Path) -> Result<(), ssh2::Error> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.keys().any(|entry| entry.parent() == Some(path)) {
+            true => Err(ssh2::Error::from_errno(ssh2::ErrorCode::SFTP(4))),
+            false => entries
+                .remove(path)
+                .map(|_| ())
+                .ok_or_else(MemoryFs::missing),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), ssh2::Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(to) {
+            return Err(ssh2::Error::from_errno(ssh2::ErrorCode::SFTP(4)));
+        }
+        let moved: Vec<PathBuf> = entries
+            .keys()
+            .filter(|entry| entry.starts_with(from))
+            .cloned()
+            .collect();
+        for entry in moved {
+            let contents = entries.remove(&entry).unwrap();
+            entries.insert(to.join(entry.strip_prefix(from).unwrap()), contents);
+        }
+        Ok(())
+    }
+
+    fn open_write(
+        &self,
+        path: &Path,
+        truncate: bool,
+    ) -> Result<Box<dyn RemoteWriter>, ssh2::Error> {
+        if path
+            .parent()
+            .is_some_and(|parent| self.stat(parent).is_err())
+        {
+            return Err(MemoryFs::missing());
+        }
+        let data = match truncate {
+            true => Vec::new(),
+            false => self.read(path).unwrap_or_default(),
+        };
+        self.write(path, &data);
+        Ok(Box::new(MemoryFile {
+            fs: self.clone(),
+            path: path.to_path_buf(),
+            data: std::io::Cursor::new(data),
+        }))
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read>, ssh2::Error> {
+        match self.read(path) {
+            Some(data) => Ok(Box::new(std::io::Cursor::new(data))),
+            None => Err(MemoryFs::missing()),
+        }
+    }
+}
+
+#[cfg(test)]
+fn memory_client(fs: &MemoryFs) -> SftpClient {
+    SftpClient {
+        config: SftpConfig {
+            host: String::from("localhost"),
+            port: default_port(),
+            user: String::from("minecraft"),
+            path: String::from("/srv/backups"),
+            key_file: None,
+            password: None,
+            max_backups: default_max_backups(),
+        },
+        _session: None,
+        fs: Box::new(fs.clone()),
+    }
+}
+
+#[test]
+pub fn test_sftp_resume_and_prune() -> std::io::Result<()> {
+    use std::sync::atomic::Ordering;
+
+    let root = std::env::temp_dir().join("crucible-test-sftp");
+    let _ = std::fs::remove_dir_all(&root);
+    let level: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+    for name in ["2024-06-01 12-00-00", "2024-06-02 12-00-00"] {
+        let snapshot = root.join("local").join(name);
+        std::fs::create_dir_all(snapshot.join("saves"))?;
+        std::fs::write(snapshot.join("saves").join("level.dat"), &level)?;
+        std::fs::write(snapshot.join("options.txt"), b"fov:90")?;
+    }
+
+    // One upload stopped partway through its first file, another's snapshot is gone locally.
+    let fs = MemoryFs::default();
+    let client = memory_client(&fs);
+    let remote = Path::new("/srv/backups");
+    let staging = remote.join("2024-06-01 12-00-00.partial");
+    client.mkdir_all(&staging.join("saves")).unwrap();
+    fs.write(&staging.join("saves").join("level.dat"), &level[..400]);
+    fs.write(&staging.join("options.txt"), b"fov:90");
+    let orphan = remote.join("2024-05-31 12-00-00.partial");
+    client.mkdir_all(&orphan).unwrap();
+    fs.write(&orphan.join("options.txt"), b"fov:70");
+
+    let stats = client
+        .write_snapshot(
+            "2024-06-02 12-00-00",
+            &root.join("local").join("2024-06-02 12-00-00"),
+        )
+        .unwrap();
+    assert_eq!((stats.files, stats.bytes), (2, 1006));
+    // Only the rest of the interrupted file was sent again, along with the new snapshot.
+    assert_eq!(fs.written.load(Ordering::Relaxed), 600 + 1006);
+    assert_eq!(
+        fs.read(
+            &remote
+                .join("2024-06-01 12-00-00")
+                .join("saves")
+                .join("level.dat")
+        ),
+        Some(level.clone())
+    );
+    assert!(client.stat(&orphan).is_none());
+    assert_eq!(
+        client.list().unwrap(),
+        vec!["2024-06-01 12-00-00", "2024-06-02 12-00-00"]
+    );
+    let stat = BackupStore::stat(&client, "2024-06-02 12-00-00").unwrap();
+    assert_eq!((stat.files, stat.bytes), (2, 1006));
+
+    let restored = root.join("restored");
+    client
+        .read_snapshot("2024-06-01 12-00-00", &restored)
+        .unwrap();
+    assert_eq!(
+        std::fs::read(restored.join("saves").join("level.dat"))?,
+        level
+    );
+
+    assert_eq!(client.prune(1).unwrap(), vec!["2024-06-01 12-00-00"]);
+    assert_eq!(client.list().unwrap(), vec!["2024-06-02 12-00-00"]);
+
+    // Writes the server lost are caught by the size check, and the upload isn't finished.
+    fs.lossy.store(true, Ordering::Relaxed);
+    let snapshot = root.join("local").join("2024-06-01 12-00-00");
+    assert!(matches!(
+        upload_snapshot(&client, "2024-06-01 12-00-00", &snapshot),
+        Err(SftpError::Mismatch(_))
+    ));
+    assert_eq!(client.list().unwrap(), vec!["2024-06-02 12-00-00"]);
+
+    // A name the server makes up to lead out of the snapshot is refused.
+    fs.write(&remote.join("2024-06-02 12-00-00").join(".."), b"escape");
+    assert!(matches!(
+        download_snapshot(&client, "2024-06-02 12-00-00", &root.join("unsafe")),
+        Err(SftpError::UnsafeName(_))
+    ));
+
+    std::fs::remove_dir_all(&root)
+}
@@ -0,0 +1,596 @@
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{
+    prelude::{DateTime, Local},
+    TimeZone,
+};
+
+use crate::{
+    app::{copy_dir_all, delete_backup, BackupError, BackupResult, CopyStats},
+    copy::measure,
+};
+
+/// Appended to a snapshot's name while it's being written, so an unfinished one is never listed.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Appended to a snapshot's name while a newer one with the same name replaces it.
+const REPLACED_SUFFIX: &str = ".replaced";
+
+/// Whether `part`, one name in a path read back from a remote store, stays inside the folder it's
+/// joined onto: it isn't empty, `.` or `..`, absolute or drive-prefixed, and holds no separator.
+pub fn is_safe_component(part: &str) -> bool {
+    let mut components = Path::new(part).components();
+    !part.contains(['/', '\\'])
+        && matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+}
+
+/// Joins the `/`-separated `relative` path read back from a remote store onto `dst`, or `None` if
+/// any part of it could lead outside `dst`.
+pub fn join_remote_path(dst: &Path, relative: &str) -> Option<PathBuf> {
+    relative
+        .split('/')
+        .try_fold(dst.to_path_buf(), |path, part| {
+            is_safe_component(part).then(|| path.join(part))
+        })
+}
+
+/// Name for a snapshot taken at `at`, as `YYYY-MM-DD HH-MM-SS +HHMM`.
+///
+/// The UTC offset keeps the repeated hour when clocks fall back from producing the same names
+/// twice.
+pub fn snapshot_name<Tz: TimeZone>(at: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    at.format("%Y-%m-%d %H-%M-%S %z").to_string()
+}
+
+/// When the snapshot called `name` was taken, if `name` follows the snapshot naming.
+///
+/// Names without an offset, as written by older versions, are read as local time.
+pub fn parse_snapshot_name(name: &str) -> Option<DateTime<Local>> {
+    if let Ok(taken) = DateTime::parse_from_str(name, "%Y-%m-%d %H-%M-%S %z") {
+        return Some(taken.with_timezone(&Local));
+    }
+    let parts = name
+        .split(['-', ' '])
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match parts[..] {
+        [year, month, day, hour, minute, second] => Local
+            .with_ymd_and_hms(year as i32, month, day, hour, minute, second)
+            .earliest(),
+        _ => None,
+    }
+}
+
+/// Whether `name` follows the snapshot naming.
+pub fn is_snapshot_name(name: &str) -> bool {
+    parse_snapshot_name(name).is_some()
+}
+
+/// Puts snapshot names in the order they were taken, which around a DST change isn't the order
+/// of the names themselves.
+pub fn sort_snapshot_names(names: &mut [String]) {
+    names.sort_by_cached_key(|name| (parse_snapshot_name(name), name.clone()));
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SnapshotStat {
+    pub taken: Option<DateTime<Local>>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Somewhere snapshots are kept, addressed by name.
+///
+/// Snapshots go in and come out as local folders, so a store is free to keep them in whatever
+/// form suits it.
+pub trait BackupStore {
+    /// Names of the stored snapshots, oldest first.
+    fn list(&self) -> BackupResult<Vec<String>>;
+
+    /// Stores the folder `src` as the snapshot `name`.
+    fn write_snapshot(&self, name: &str, src: &Path) -> BackupResult<CopyStats>;
+
+    /// Copies the snapshot `name` out into the folder `dst`.
+    fn read_snapshot(&self, name: &str, dst: &Path) -> BackupResult<CopyStats>;
+
+    fn delete(&self, name: &str) -> BackupResult<()>;
+
+    fn stat(&self, name: &str) -> BackupResult<SnapshotStat>;
+
+    /// The folder holding the snapshot `name`, for stores that keep snapshots as local folders
+    /// and so can be read from in place.
+    fn local_path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// Deletes the oldest snapshots beyond `keep`, returning their names.
+    fn prune(&self, keep: usize) -> BackupResult<Vec<String>> {
+        let names = self.list()?;
+        let excess = names.len().saturating_sub(keep);
+        for name in &names[..excess] {
+            self.delete(name)?;
+        }
+        Ok(names[..excess].to_vec())
+    }
+}
+
+/// Snapshots as plain folders under `root`, the layout `back_up_files` writes.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> LocalStore {
+        LocalStore { root: root.into() }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Where the snapshot `name` is written before `finalize` moves it into place.
+    pub fn staging_path(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}{}", name, PARTIAL_SUFFIX))
+    }
+
+    /// Renames the finished snapshot `name` from its staging folder to its real one, replacing
+    /// a snapshot taken within the same second.
+    ///
+    /// The snapshot being replaced is moved aside until the rename has gone through, so a failed
+    /// rename keeps it.
+    pub fn finalize(&self, name: &str) -> BackupResult<()> {
+        let rename = |from: &Path, to: &Path| {
+            std::fs::rename(from, to).map_err(|source| BackupError::Rename {
+                from: from.to_path_buf(),
+                to: to.to_path_buf(),
+                source,
+            })
+        };
+        let (from, to) = (self.staging_path(name), self.path(name));
+        if !to.exists() {
+            return rename(&from, &to);
+        }
+        let aside = self.root.join(format!("{}{}", name, REPLACED_SUFFIX));
+        if aside.exists() {
+            delete_backup(&aside)?;
+        }
+        rename(&to, &aside)?;
+        if let Err(e) = rename(&from, &to) {
+            let _ = std::fs::rename(&aside, &to);
+            return Err(e);
+        }
+        delete_backup(&aside)
+    }
+
+    /// Removes the staging folders of snapshots that were never finished, e.g. because of a crash,
+    /// returning their paths.
+    ///
+    /// A snapshot left moved aside by an interrupted `finalize` is put back if nothing replaced it.
+    pub fn clean_partial(&self) -> BackupResult<Vec<PathBuf>> {
+        let read_error = |source| BackupError::ReadDir {
+            path: self.root.clone(),
+            source,
+        };
+        let mut removed = Vec::new();
+        for entry in std::fs::read_dir(&self.root).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let partial = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(PARTIAL_SUFFIX))
+                .is_some_and(is_snapshot_name);
+            if partial && entry.path().is_dir() {
+                delete_backup(&entry.path())?;
+                removed.push(entry.path());
+            }
+            let replaced = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(REPLACED_SUFFIX))
+                .filter(|name| is_snapshot_name(name))
+                .map(|name| self.path(name));
+            match replaced {
+                Some(snapshot) if entry.path().is_dir() && snapshot.exists() => {
+                    delete_backup(&entry.path())?;
+                    removed.push(entry.path());
+                }
+                Some(snapshot) if entry.path().is_dir() => {
+                    std::fs::rename(entry.path(), &snapshot).map_err(|source| {
+                        BackupError::Rename {
+                            from: entry.path(),
+                            to: snapshot,
+                            source,
+                        }
+                    })?;
+                }
+                _ => {}
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl BackupStore for LocalStore {
+    fn list(&self) -> BackupResult<Vec<String>> {
+        let read_error = |source| BackupError::ReadDir {
+            path: self.root.clone(),
+            source,
+        };
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.root).map_err(read_error)? {
+            let entry = entry.map_err(read_error)?;
+            let is_dir = entry
+                .file_type()
+                .map_err(|source| BackupError::ReadDir {
+                    path: entry.path(),
+                    source,
+                })?
+                .is_dir();
+            if let Some(name) = entry.file_name().to_str() {
+                if is_dir && is_snapshot_name(name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        sort_snapshot_names(&mut names);
+        Ok(names)
+    }
+
+    fn write_snapshot(&self, name: &str, src: &Path) -> BackupResult<CopyStats> {
+        let staging = self.staging_path(name);
+        // A snapshot built in its own staging folder only has to be moved into place.
+        if src == staging {
+            let stats = measure(&staging)?;
+            self.finalize(name)?;
+            return Ok(stats);
+        }
+        if staging.exists() {
+            delete_backup(&staging)?;
+        }
+        match copy_dir_all(src, &staging).and_then(|stats| self.finalize(name).map(|_| stats)) {
+            Ok(stats) => Ok(stats),
+            Err(e) => {
+                let _ = delete_backup(&staging);
+                Err(e)
+            }
+        }
+    }
+
+    fn read_snapshot(&self, name: &str, dst: &Path) -> BackupResult<CopyStats> {
+        copy_dir_all(self.path(name), dst)
+    }
+
+    fn delete(&self, name: &str) -> BackupResult<()> {
+        delete_backup(&self.path(name))
+    }
+
+    fn local_path(&self, name: &str) -> Option<PathBuf> {
+        Some(self.path(name))
+    }
+
+    fn stat(&self, name: &str) -> BackupResult<SnapshotStat> {
+        let path = self.path(name);
+        if !path.is_dir() {
+            return Err(BackupError::MissingSnapshot(name.to_string()));
+        }
+        let stats = measure(&path)?;
+        Ok(SnapshotStat {
+            taken: parse_snapshot_name(name),
+            files: stats.files,
+            bytes: stats.bytes,
+        })
+    }
+}
+
+/// Snapshots held in memory, file by file, for exercising code that takes a `BackupStore`.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    snapshots: std::sync::Mutex<
+        std::collections::BTreeMap<String, std::collections::BTreeMap<PathBuf, Vec<u8>>>,
+    >,
+}
+
+#[cfg(test)]
+fn read_tree(
+    dir: &Path,
+    relative: &Path,
+    files: &mut std::collections::BTreeMap<PathBuf, Vec<u8>>,
+) -> BackupResult<()> {
+    let path = dir.join(relative);
+    let read_error = |source| BackupError::ReadDir {
+        path: path.clone(),
+        source,
+    };
+    for entry in std::fs::read_dir(&path).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let name = relative.join(entry.file_name());
+        if entry.path().is_dir() {
+            read_tree(dir, &name, files)?;
+        } else {
+            let body = std::fs::read(entry.path()).map_err(|source| BackupError::ReadFile {
+                path: entry.path(),
+                source,
+            })?;
+            files.insert(name, body);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+impl BackupStore for MemoryStore {
+    fn list(&self) -> BackupResult<Vec<String>> {
+        let mut names: Vec<String> = self.snapshots.lock().unwrap().keys().cloned().collect();
+        sort_snapshot_names(&mut names);
+        Ok(names)
+    }
+
+    fn write_snapshot(&self, name: &str, src: &Path) -> BackupResult<CopyStats> {
+        let mut files = std::collections::BTreeMap::new();
+        read_tree(src, Path::new(""), &mut files)?;
+        let stats = CopyStats {
+            files: files.len() as u64,
+            bytes: files.values().map(|body| body.len() as u64).sum(),
+            ..Default::default()
+        };
+        self.snapshots
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), files);
+        Ok(stats)
+    }
+
+    fn read_snapshot(&self, name: &str, dst: &Path) -> BackupResult<CopyStats> {
+        let snapshots = self.snapshots.lock().unwrap();
+        let files = snapshots
+            .get(name)
+            .ok_or_else(|| BackupError::MissingSnapshot(name.to_string()))?;
+        let mut stats = CopyStats::default();
+        for (relative, body) in files {
+            let path = dst.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|source| BackupError::CreateDir {
+                    path: parent.to_path_buf(),
+                    source,
+                })?;
+            }
+            std::fs::write(&path, body)
+                .map_err(|source| BackupError::WriteFile { path, source })?;
+            stats.files += 1;
+            stats.bytes += body.len() as u64;
+        }
+        Ok(stats)
+    }
+
+    fn delete(&self, name: &str) -> BackupResult<()> {
+        match self.snapshots.lock().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(BackupError::MissingSnapshot(name.to_string())),
+        }
+    }
+
+    fn stat(&self, name: &str) -> BackupResult<SnapshotStat> {
+        let snapshots = self.snapshots.lock().unwrap();
+        let files = snapshots
+            .get(name)
+            .ok_or_else(|| BackupError::MissingSnapshot(name.to_string()))?;
+        Ok(SnapshotStat {
+            taken: parse_snapshot_name(name),
+            files: files.len() as u64,
+            bytes: files.values().map(|body| body.len() as u64).sum(),
+        })
+    }
+}
+
+#[test]
+pub fn test_parse_snapshot_name() {
+    assert_eq!(
+        parse_snapshot_name("2024-06-01 12-30-00"),
+        Local.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).earliest()
+    );
+    assert_eq!(
+        parse_snapshot_name("2024-06-01 12-30-00 -0500"),
+        Some(
+            chrono::Utc
+                .with_ymd_and_hms(2024, 6, 1, 17, 30, 0)
+                .unwrap()
+                .into()
+        )
+    );
+    for name in [
+        "2024-06-01",
+        "2024-13-01 12-30-00",
+        "backup-report.json",
+        "a-b-c d-e-f",
+        "2024-06-01 12-30-00 +02",
+    ] {
+        assert!(!is_snapshot_name(name), "{}", name);
+    }
+}
+
+#[test]
+pub fn test_join_remote_path() {
+    let dst = Path::new("restored");
+    assert_eq!(
+        join_remote_path(dst, "saves/world/level.dat"),
+        Some(dst.join("saves").join("world").join("level.dat"))
+    );
+    for relative in [
+        "../escape.txt",
+        "saves/../../escape.txt",
+        "saves//level.dat",
+        "./level.dat",
+        "",
+        "/etc/passwd",
+        "saves\\..\\escape.txt",
+    ] {
+        assert_eq!(join_remote_path(dst, relative), None, "{}", relative);
+    }
+    if cfg!(windows) {
+        assert_eq!(join_remote_path(dst, "C:/escape.txt"), None);
+    }
+}
+
+#[test]
+pub fn test_snapshot_names_across_dst() {
+    use chrono_tz::America::New_York;
+    let at = |hour, minute| {
+        chrono::Utc
+            .with_ymd_and_hms(2024, 3, 10, hour, minute, 0)
+            .unwrap()
+    };
+
+    // Spring forward: 01:59 EST is followed a minute later by 03:00 EDT.
+    let before = snapshot_name(&at(6, 59).with_timezone(&New_York));
+    let after = snapshot_name(&at(7, 0).with_timezone(&New_York));
+    assert_eq!(before, "2024-03-10 01-59-00 -0500");
+    assert_eq!(after, "2024-03-10 03-00-00 -0400");
+    assert_eq!(parse_snapshot_name(&before), Some(at(6, 59).into()));
+    assert_eq!(parse_snapshot_name(&after), Some(at(7, 0).into()));
+
+    // Fall back: 01:30 EDT comes before 01:10 EST, which the wall clock alone gets backwards.
+    let at = |hour, minute| {
+        chrono::Utc
+            .with_ymd_and_hms(2024, 11, 3, hour, minute, 0)
+            .unwrap()
+    };
+    let taken = [at(5, 10), at(5, 30), at(6, 10), at(6, 30)];
+    let mut names: Vec<String> = taken
+        .iter()
+        .map(|at| snapshot_name(&at.with_timezone(&New_York)))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "2024-11-03 01-10-00 -0400",
+            "2024-11-03 01-30-00 -0400",
+            "2024-11-03 01-10-00 -0500",
+            "2024-11-03 01-30-00 -0500",
+        ]
+    );
+    let expected = names.clone();
+    names.reverse();
+    sort_snapshot_names(&mut names);
+    assert_eq!(names, expected);
+    for (name, at) in names.iter().zip(taken) {
+        assert_eq!(parse_snapshot_name(name), Some(at.into()));
+    }
+}
+
+#[test]
+pub fn test_backup_stores() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-stores");
+    let _ = std::fs::remove_dir_all(&root);
+    let src = root.join("src");
+    std::fs::create_dir_all(src.join("saves").join("world"))?;
+    std::fs::write(src.join("saves").join("world").join("level.dat"), b"level")?;
+    std::fs::write(src.join("options.txt"), b"fov:90")?;
+    std::fs::create_dir_all(root.join("local").join("not a snapshot"))?;
+    std::fs::create_dir_all(root.join("local").join("2024-05-31 12-00-00.partial"))?;
+
+    let stores: [(&str, Box<dyn BackupStore>); 2] = [
+        ("local", Box::new(LocalStore::new(root.join("local")))),
+        ("memory", Box::new(MemoryStore::default())),
+    ];
+    for (kind, store) in stores {
+        for name in ["2024-06-02 12-00-00", "2024-06-01 12-00-00"] {
+            let stats = store.write_snapshot(name, &src).unwrap();
+            assert_eq!((stats.files, stats.bytes), (2, 11), "{}", kind);
+        }
+        assert_eq!(
+            store.list().unwrap(),
+            vec!["2024-06-01 12-00-00", "2024-06-02 12-00-00"],
+            "{}",
+            kind
+        );
+        let stat = store.stat("2024-06-02 12-00-00").unwrap();
+        assert_eq!((stat.files, stat.bytes), (2, 11), "{}", kind);
+        assert!(matches!(
+            store.stat("2024-06-03 12-00-00"),
+            Err(BackupError::MissingSnapshot(_))
+        ));
+
+        let dst = root.join(format!("restored-{}", kind));
+        store.read_snapshot("2024-06-02 12-00-00", &dst).unwrap();
+        assert_eq!(
+            std::fs::read(dst.join("saves").join("world").join("level.dat"))?,
+            b"level"
+        );
+
+        assert_eq!(
+            store.prune(1).unwrap(),
+            vec!["2024-06-01 12-00-00"],
+            "{}",
+            kind
+        );
+        store.delete("2024-06-02 12-00-00").unwrap();
+        assert!(store.list().unwrap().is_empty(), "{}", kind);
+    }
+
+    // A snapshot built in the staging folder is moved into place rather than copied.
+    let store = LocalStore::new(root.join("local"));
+    let staging = store.staging_path("2024-06-04 12-00-00");
+    std::fs::create_dir_all(&staging)?;
+    std::fs::write(staging.join("options.txt"), b"fov:90")?;
+    let stats = store
+        .write_snapshot("2024-06-04 12-00-00", &staging)
+        .unwrap();
+    assert_eq!((stats.files, stats.bytes), (1, 6));
+    assert!(!staging.exists());
+    assert_eq!(
+        store.local_path("2024-06-04 12-00-00"),
+        Some(root.join("local").join("2024-06-04 12-00-00"))
+    );
+    assert_eq!(store.list().unwrap(), vec!["2024-06-04 12-00-00"]);
+    std::fs::remove_dir_all(&root)
+}
+
+#[test]
+pub fn test_clean_partial() -> std::io::Result<()> {
+    let root = std::env::temp_dir().join("crucible-test-store-partial");
+    let _ = std::fs::remove_dir_all(&root);
+    let store = LocalStore::new(&root);
+    std::fs::create_dir_all(store.staging_path("2024-06-01 12-00-00").join("saves"))?;
+    std::fs::create_dir_all(store.path("2024-06-02 12-00-00"))?;
+    std::fs::create_dir_all(root.join("mods.partial"))?;
+
+    assert_eq!(store.list().unwrap(), vec!["2024-06-02 12-00-00"]);
+    assert_eq!(
+        store.clean_partial().unwrap(),
+        vec![store.staging_path("2024-06-01 12-00-00")]
+    );
+    assert!(!store.staging_path("2024-06-01 12-00-00").exists());
+    // Only staging folders named like snapshots are ours to remove.
+    assert!(root.join("mods.partial").is_dir());
+
+    // A rename that fails keeps the snapshot it would have replaced.
+    let name = "2024-06-02 12-00-00";
+    std::fs::write(store.path(name).join("level.dat"), b"old")?;
+    assert!(store.finalize(name).is_err());
+    assert_eq!(std::fs::read(store.path(name).join("level.dat"))?, b"old");
+    std::fs::create_dir_all(store.staging_path(name))?;
+    std::fs::write(store.staging_path(name).join("level.dat"), b"new")?;
+    store.finalize(name).unwrap();
+    assert_eq!(std::fs::read(store.path(name).join("level.dat"))?, b"new");
+    assert_eq!(store.list().unwrap(), vec![name]);
+
+    // One left aside by a crash comes back, unless its replacement made it into place.
+    std::fs::rename(store.path(name), root.join(format!("{}.replaced", name)))?;
+    std::fs::create_dir_all(root.join("2024-06-03 12-00-00.replaced"))?;
+    std::fs::create_dir_all(store.path("2024-06-03 12-00-00"))?;
+    assert_eq!(
+        store.clean_partial().unwrap(),
+        vec![root.join("2024-06-03 12-00-00.replaced")]
+    );
+    assert_eq!(std::fs::read(store.path(name).join("level.dat"))?, b"new");
+    assert_eq!(store.list().unwrap(), vec![name, "2024-06-03 12-00-00"]);
+
+    std::fs::remove_dir_all(&root)
+}
@@ -1,21 +1,29 @@
 use std::path::PathBuf;
 
+use chrono::Local;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
     symbols::{border, line},
     text::{Line, Span},
-    widgets::{block, Block, Borders, List, ListState, Paragraph},
+    widgets::{block, Block, Borders, Gauge, List, ListState, Paragraph, Wrap},
     Frame,
 };
 
-use crate::app::{
-    get_backups_sorted, Action, App, CurrentScreen, TIPS_BACKUPS, TIPS_CONFIRM, TIPS_MAIN,
-    TIPS_NUM, TIPS_PATH, TIPS_SETTINGS, TIPS_TARGETS, TITLE,
+use crate::{
+    app::{
+        bytes_to_readable, get_backups_sorted, Action, App, BackupStatus, CurrentScreen, Location,
+        Pause, RestoreStatus, TIPS_BACKUPS, TIPS_CONFIRM, TIPS_HISTORY, TIPS_MAIN, TIPS_NUM,
+        TIPS_PASSPHRASE, TIPS_PATH, TIPS_QUIT, TIPS_SETTINGS, TIPS_TARGETS, TIPS_TEXT, TITLE,
+    },
+    copy::Operation,
+    crypto::PASSPHRASE_VAR,
+    history::{HistoryEntry, HistoryKind},
 };
 
 pub const BACKUPS_MAX_CHARS: usize = 3;
 pub const BACKUPS_FREQ_CHARS: usize = 6;
+pub const BACKUPS_PAUSE_CHARS: usize = 2;
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -89,8 +97,13 @@ pub fn ui(
             CurrentScreen::Target => TIPS_PATH,
             CurrentScreen::Frequency => TIPS_NUM,
             CurrentScreen::Max => TIPS_NUM,
+            CurrentScreen::Schedule => TIPS_TEXT,
+            CurrentScreen::Pause => TIPS_NUM,
+            CurrentScreen::Passphrase => TIPS_PASSPHRASE,
+            CurrentScreen::History => TIPS_HISTORY,
         }
-        .map(|(key, rest)| {
+        .iter()
+        .map(|&(key, rest)| {
             if key.len() > 0 {
                 Line::from(vec![
                     Span::styled(
@@ -113,7 +126,7 @@ pub fn ui(
                 Line::styled("", Style::default())
             }
         })
-        .to_vec(),
+        .collect::<Vec<_>>(),
     )
     .alignment(Alignment::Left)
     .block(tooltips);
@@ -124,31 +137,394 @@ pub fn ui(
         CurrentScreen::Targets => Block::default().borders(Borders::ALL).title(
             block::Title::from(" Target Files and Folders ".not_bold()).alignment(Alignment::Left),
         ),
-        CurrentScreen::Settings | CurrentScreen::Frequency | CurrentScreen::Max => Block::default()
+        CurrentScreen::Settings
+        | CurrentScreen::Frequency
+        | CurrentScreen::Max
+        | CurrentScreen::Schedule => Block::default()
             .borders(Borders::ALL)
             .title(block::Title::from(" Settings ".not_bold()).alignment(Alignment::Left)),
         CurrentScreen::Target => Block::default()
             .borders(Borders::ALL)
             .title(block::Title::from(" Choose Path ".not_bold()).alignment(Alignment::Center)),
+        CurrentScreen::History => Block::default().borders(Borders::ALL).title(
+            block::Title::from(
+                format!(
+                    " History ({}) ",
+                    match ui_state.history_filter {
+                        Some(kind) => kind.label(),
+                        None => "all",
+                    }
+                )
+                .not_bold(),
+            )
+            .alignment(Alignment::Left),
+        ),
+        CurrentScreen::Main | CurrentScreen::Pause | CurrentScreen::Passphrase
+            if app.backup_status.is_alert() =>
+        {
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(border::DOUBLE)
+                .border_style(Style::default().fg(Color::LightRed))
+                .title(
+                    block::Title::from(
+                        " Automatic backups keep failing! "
+                            .bold()
+                            .fg(Color::LightRed),
+                    )
+                    .alignment(Alignment::Left),
+                )
+        }
+        CurrentScreen::Main | CurrentScreen::Pause | CurrentScreen::Passphrase => Block::default()
+            .borders(Borders::ALL)
+            .title(block::Title::from(" Status ".not_bold()).alignment(Alignment::Left)),
         _ => Block::default().borders(Borders::ALL),
     };
 
     frame.render_widget(tiptext, horiz_chunks[0]);
 
     match app.current_screen {
+        CurrentScreen::Main | CurrentScreen::Pause | CurrentScreen::Passphrase => {
+            let mut lines =
+                match &app.backup_status {
+                    BackupStatus::Idle => vec![Line::from(" No backups have run this session.")],
+                    BackupStatus::Succeeded(at, report) => {
+                        let mut lines = vec![Line::from(vec![
+                            Span::raw(" Last backup succeeded at "),
+                            Span::styled(
+                                at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                                Style::default().fg(Color::LightGreen),
+                            ),
+                        ])];
+                        lines.extend(report.to_ui_lines().into_iter().enumerate().map(
+                            |(i, line)| match i {
+                                0 => Line::from(format!(" {}", line)),
+                                _ => Line::styled(
+                                    format!("   {}", line),
+                                    Style::default().fg(Color::LightYellow),
+                                ),
+                            },
+                        ));
+                        lines
+                    }
+                    BackupStatus::Failed {
+                        at,
+                        error,
+                        attempts,
+                        retry_at,
+                    } => vec![
+                        Line::from(vec![
+                            Span::raw(" Backup failed at "),
+                            Span::styled(
+                                at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                                Style::default().fg(Color::LightRed),
+                            ),
+                            Span::raw(format!(" ({} in a row)", attempts)),
+                        ]),
+                        Line::styled(format!(" {}", error), Style::default().fg(Color::LightRed)),
+                        Line::from(format!(
+                            " Retrying at {}",
+                            retry_at.format("%Y-%m-%d %H:%M:%S")
+                        )),
+                    ],
+                };
+            match app.game_running {
+                Some(true) => lines.insert(0, Line::from(" Minecraft is running.")),
+                Some(false) => lines.insert(
+                    0,
+                    Line::styled(
+                        " Minecraft is not running; scheduled backups are on hold.",
+                        Style::default().fg(Color::LightYellow),
+                    ),
+                ),
+                None => {}
+            }
+            match app.configuration.pause {
+                Some(Pause::Until(until)) if until > Local::now() => lines.insert(
+                    0,
+                    Line::styled(
+                        format!(
+                            " Automatic backups are paused until {}.",
+                            until.format("%Y-%m-%d %H:%M:%S")
+                        ),
+                        Style::default().fg(Color::LightYellow),
+                    ),
+                ),
+                Some(Pause::Indefinitely) => lines.insert(
+                    0,
+                    Line::styled(
+                        " Automatic backups are paused until resumed.",
+                        Style::default().fg(Color::LightYellow),
+                    ),
+                ),
+                _ => {}
+            }
+            match &app.last_restore {
+                Some(RestoreStatus::Succeeded(at, stats)) => lines.insert(
+                    0,
+                    Line::from(format!(
+                        " Last restore finished at {} ({} files, {})",
+                        at.format("%Y-%m-%d %H:%M:%S"),
+                        stats.files,
+                        bytes_to_readable(stats.bytes)
+                    )),
+                ),
+                Some(RestoreStatus::Failed(at, error)) => lines.insert(
+                    0,
+                    Line::styled(
+                        format!(
+                            " Restore failed at {}: {}",
+                            at.format("%Y-%m-%d %H:%M:%S"),
+                            error
+                        ),
+                        Style::default().fg(Color::LightRed),
+                    ),
+                ),
+                None => {}
+            }
+            let progress = app.progress.get();
+            if ui_state.quit_when_idle {
+                lines.insert(
+                    0,
+                    Line::styled(
+                        " Quitting once this finishes.",
+                        Style::default().fg(Color::LightYellow),
+                    ),
+                );
+            }
+            if let Some(report) = &progress {
+                let current = report
+                    .current
+                    .as_ref()
+                    .and_then(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                lines.insert(
+                    0,
+                    Line::styled(
+                        format!(" {}: {}", report.operation.label(), current),
+                        Style::default().fg(Color::LightCyan),
+                    ),
+                );
+            }
+            let inner = mainblock.inner(horiz_chunks[1]);
+            let contents = Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .block(mainblock);
+            frame.render_widget(contents, horiz_chunks[1]);
+            if let Some(report) = progress {
+                let area = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(3)])
+                    .split(inner)[1]
+                    .inner(Margin::new(1, 0));
+                let gauge = Gauge::default()
+                    .block(Block::default().borders(Borders::ALL))
+                    .gauge_style(Style::default().fg(Color::LightCyan))
+                    .ratio(report.ratio())
+                    .label(format!(
+                        "{} of {} files, {} of {}",
+                        report.done.files,
+                        report.total.files,
+                        bytes_to_readable(report.done.bytes),
+                        bytes_to_readable(report.total.bytes)
+                    ));
+                frame.render_widget(gauge, area);
+            }
+            if app.current_screen == CurrentScreen::Pause {
+                let center = centered_rect(33, 33, frame.area());
+                let numeric = Block::default()
+                    .borders(Borders::ALL)
+                    .title(
+                        block::Title::from(
+                            " Pause For "
+                                .bold()
+                                .style(Style::default().fg(Color::White)),
+                        )
+                        .alignment(Alignment::Center),
+                    )
+                    .border_set(border::DOUBLE)
+                    .border_style(Style::default().fg(Color::White).bg(Color::Blue))
+                    .style(Style::default().bg(Color::Blue));
+                let label = Paragraph::new(format!(
+                    "\n Hours: {}\n\n 00 pauses until resumed",
+                    ui_state.num_buf.join("")
+                ))
+                .alignment(Alignment::Left)
+                .style(Style::default().fg(Color::White))
+                .block(numeric);
+                frame.set_cursor_position(Position::new(
+                    center.x + ui_state.cursor as u16 + 9,
+                    center.y + 2,
+                ));
+                frame.render_widget(label, center);
+            }
+            if app.current_screen == CurrentScreen::Passphrase {
+                let center = centered_rect(50, 33, frame.area());
+                let input = Block::default()
+                    .borders(Borders::ALL)
+                    .title(
+                        block::Title::from(
+                            " Enter Passphrase "
+                                .bold()
+                                .style(Style::default().fg(Color::White)),
+                        )
+                        .alignment(Alignment::Center),
+                    )
+                    .border_set(border::DOUBLE)
+                    .border_style(Style::default().fg(Color::White).bg(Color::Blue))
+                    .style(Style::default().bg(Color::Blue));
+                let prompt = match ui_state.passphrase_first {
+                    Some(_) => "Confirm passphrase",
+                    None => "Passphrase",
+                };
+                let mut lines = vec![
+                    Line::from(""),
+                    Line::from(format!(
+                        " {}: {}",
+                        prompt,
+                        "*".repeat(ui_state.text_buf.chars().count())
+                    )),
+                    Line::from(""),
+                    Line::from(" Encrypts new backups and unlocks encrypted ones."),
+                    Line::from(format!(" Set {} to skip this prompt.", PASSPHRASE_VAR)),
+                ];
+                if let Some(error) = &ui_state.text_error {
+                    lines.push(Line::styled(
+                        format!(" {}", error),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                }
+                let label = Paragraph::new(lines)
+                    .alignment(Alignment::Left)
+                    .style(Style::default().fg(Color::White))
+                    .wrap(Wrap { trim: false })
+                    .block(input);
+                frame.set_cursor_position(Position::new(
+                    center.x + (ui_state.text_buf.chars().count() + prompt.len()) as u16 + 4,
+                    center.y + 2,
+                ));
+                frame.render_widget(label, center);
+            }
+        }
         CurrentScreen::Backups => {
-            let backups = get_backups_sorted(&app.configuration).unwrap();
-            let items = backups
+            let backups_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(horiz_chunks[1]);
+            let items = app
+                .backups
                 .iter()
-                .map(|b| b.1.file_name().unwrap().to_str().unwrap());
+                .map(|(location, name)| match location {
+                    Location::Local => name.clone(),
+                    Location::Remote(remote) => format!("{} ({})", name, remote.kind()),
+                })
+                .chain(
+                    app.remotes_loading
+                        .then(|| String::from("Loading remote backups…")),
+                );
             let contents = List::new(items)
                 .block(mainblock)
                 .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
                 .highlight_symbol(" => ")
                 .repeat_highlight_symbol(true);
-            frame.render_stateful_widget(contents, horiz_chunks[1], &mut ui_state.backups);
+            frame.render_stateful_widget(contents, backups_chunks[0], &mut ui_state.backups);
+
+            let selected = ui_state
+                .backups
+                .selected()
+                .and_then(|index| app.backups.get(index));
+            let mut report_lines: Vec<Line<'_>> = match selected {
+                Some((Location::Remote(remote), _)) => {
+                    vec![Line::from(format!(" Stored in {}.", remote.display()))]
+                }
+                Some((Location::Local, name)) => {
+                    let report = app
+                        .selected_report
+                        .as_ref()
+                        .filter(|(loaded, _)| loaded == name)
+                        .and_then(|(_, report)| report.as_ref());
+                    match report {
+                        Some(report) => report
+                            .to_ui_lines()
+                            .into_iter()
+                            .map(|line| Line::from(format!(" {}", line)))
+                            .collect(),
+                        None => vec![Line::from(" No report recorded for this backup.")],
+                    }
+                }
+                None => Vec::new(),
+            };
+            if let Some(error) = &app.backups_error {
+                report_lines.insert(
+                    0,
+                    Line::from(format!(" {}", error)).style(Style::default().fg(Color::LightRed)),
+                );
+            }
+            let report =
+                Paragraph::new(report_lines)
+                    .wrap(Wrap { trim: false })
+                    .block(Block::default().borders(Borders::ALL).title(
+                        block::Title::from(" Report ".not_bold()).alignment(Alignment::Left),
+                    ));
+            frame.render_widget(report, backups_chunks[1]);
+        }
+        CurrentScreen::History => {
+            let revision = app.history.revision();
+            if ui_state
+                .history_entries
+                .as_ref()
+                .is_none_or(|(loaded, _)| *loaded != revision)
+            {
+                ui_state.history_entries = Some((revision, app.history.load().unwrap_or_default()));
+            }
+            let entries: &[HistoryEntry] = match &ui_state.history_entries {
+                Some((_, entries)) => entries,
+                None => &[],
+            };
+            let items: Vec<Line<'_>> = entries
+                .iter()
+                .rev()
+                .filter(|e| match ui_state.history_filter {
+                    Some(kind) => e.kind == kind,
+                    None => true,
+                })
+                .map(|e| {
+                    Line::from(vec![
+                        Span::styled(
+                            e.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                            Style::default().fg(Color::LightCyan),
+                        ),
+                        Span::styled(
+                            format!(" {:<8}", e.kind.label()),
+                            Style::default().fg(match e.kind {
+                                HistoryKind::Error => Color::LightRed,
+                                HistoryKind::Skip | HistoryKind::Prune | HistoryKind::Cancel => {
+                                    Color::LightYellow
+                                }
+                                _ => Color::LightGreen,
+                            }),
+                        ),
+                        Span::raw(format!(
+                            "{:>7.1}s {:>7} files {:>10}  {}",
+                            e.duration.as_secs_f64(),
+                            e.files,
+                            bytes_to_readable(e.bytes),
+                            e.detail
+                        )),
+                    ])
+                })
+                .collect();
+            let contents = List::new(items)
+                .block(mainblock)
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+            frame.render_stateful_widget(contents, horiz_chunks[1], &mut ui_state.history);
         }
         CurrentScreen::Targets => {
+            let targets_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(horiz_chunks[1]);
             let items: Vec<Span<'_>> = app
                 .configuration
                 .targets
@@ -160,9 +536,63 @@ pub fn ui(
                 .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
                 .highlight_symbol(" => ")
                 .repeat_highlight_symbol(true);
-            frame.render_stateful_widget(contents, horiz_chunks[1], &mut ui_state.targets)
+            frame.render_stateful_widget(contents, targets_chunks[0], &mut ui_state.targets);
+
+            let mut preview_lines: Vec<Line<'_>> = Vec::new();
+            if let Some(index) = ui_state.targets.selected() {
+                let patterns = match app.configuration.targets.get(index) {
+                    Some(target) => app.configuration.patterns_for(target),
+                    None => Vec::new(),
+                };
+                if patterns.is_empty() {
+                    preview_lines.push(Line::from(" No patterns; every file is copied."));
+                } else {
+                    preview_lines.push(Line::from(format!(" Patterns: {}", patterns.join(" "))));
+                    preview_lines.push(Line::from(""));
+                    let previewed = app.target_preview.as_ref().filter(|previewed| {
+                        Some(&previewed.target) == app.configuration.targets.get(index)
+                            && previewed.patterns == patterns
+                    });
+                    match previewed.map(|previewed| &previewed.result) {
+                        Some(None) => {
+                            preview_lines.push(Line::from(" Working out which files match…"))
+                        }
+                        Some(Some(Ok(preview))) => {
+                            preview_lines.push(Line::from(format!(
+                                " {} files ({}) copied, {} excluded:",
+                                preview.included.files,
+                                bytes_to_readable(preview.included.bytes),
+                                preview.excluded.len(),
+                            )));
+                            preview_lines.extend(preview.excluded.iter().map(|path| {
+                                Line::styled(
+                                    format!("   {}", path.display()),
+                                    Style::default().fg(Color::Yellow),
+                                )
+                            }));
+                        }
+                        Some(Some(Err(e))) => {
+                            preview_lines.push(Line::styled(
+                                format!(" {}", e),
+                                Style::default().fg(Color::Red),
+                            ));
+                        }
+                        _ => preview_lines.push(Line::from(" Press [v] to see which files match.")),
+                    }
+                }
+            }
+            let preview =
+                Paragraph::new(preview_lines)
+                    .wrap(Wrap { trim: false })
+                    .block(Block::default().borders(Borders::ALL).title(
+                        block::Title::from(" Preview ".not_bold()).alignment(Alignment::Left),
+                    ));
+            frame.render_widget(preview, targets_chunks[1]);
         }
-        CurrentScreen::Settings | CurrentScreen::Max | CurrentScreen::Frequency => {
+        CurrentScreen::Settings
+        | CurrentScreen::Max
+        | CurrentScreen::Frequency
+        | CurrentScreen::Schedule => {
             let items: Vec<Span<'_>> = app
                 .configuration
                 .to_ui_list()
@@ -221,6 +651,44 @@ pub fn ui(
                 }
                 frame.render_widget(label, center);
             }
+            if app.current_screen == CurrentScreen::Schedule {
+                let center = centered_rect(50, 33, frame.area());
+                let input = Block::default()
+                    .borders(Borders::ALL)
+                    .title(
+                        block::Title::from(
+                            " Enter Schedule "
+                                .bold()
+                                .style(Style::default().fg(Color::White)),
+                        )
+                        .alignment(Alignment::Center),
+                    )
+                    .border_set(border::DOUBLE)
+                    .border_style(Style::default().fg(Color::White).bg(Color::Blue))
+                    .style(Style::default().bg(Color::Blue));
+                let mut lines = vec![
+                    Line::from(""),
+                    Line::from(format!(" Schedule: {}", ui_state.text_buf)),
+                    Line::from(""),
+                    Line::from(" e.g. daily 04:00, every 10m 18:00-02:00, cron 0 4 * * 1-5"),
+                ];
+                if let Some(error) = &ui_state.text_error {
+                    lines.push(Line::styled(
+                        format!(" {}", error),
+                        Style::default().fg(Color::Yellow),
+                    ));
+                }
+                let label = Paragraph::new(lines)
+                    .alignment(Alignment::Left)
+                    .style(Style::default().fg(Color::White))
+                    .wrap(Wrap { trim: false })
+                    .block(input);
+                frame.set_cursor_position(Position::new(
+                    center.x + ui_state.text_buf.chars().count() as u16 + 12,
+                    center.y + 2,
+                ));
+                frame.render_widget(label, center);
+            }
         }
         CurrentScreen::Target | CurrentScreen::Path => {
             let target_chunks = Layout::default()
@@ -270,7 +738,6 @@ pub fn ui(
                 },
             );
         }
-        _ => frame.render_widget(mainblock, horiz_chunks[1]),
     };
 
     if action == Action::ConfirmDelete || action == Action::ConfirmRestore {
@@ -320,6 +787,50 @@ pub fn ui(
         .centered()
         .block(warning);
 
+        frame.render_widget(warn_text, center);
+    } else if action == Action::ConfirmQuit {
+        let center = centered_rect(33, 33, vert_chunks[0]);
+        let warning = Block::default()
+            .borders(Borders::ALL)
+            .title(
+                block::Title::from(" Quit? ".bold().style(Style::default().fg(Color::White)))
+                    .alignment(Alignment::Center)
+                    .position(block::Position::Top),
+            )
+            .title(
+                block::Title::from(Line::from(
+                    TIPS_QUIT
+                        .map(|(key, rest)| {
+                            vec![
+                                " [".fg(Color::Rgb(185, 185, 185)).not_bold(),
+                                key.fg(Color::Rgb(235, 235, 235)).bold(),
+                                "]".fg(Color::Rgb(185, 185, 185)).not_bold(),
+                                rest.fg(Color::Rgb(185, 185, 185)).not_bold(),
+                                " ".fg(Color::Rgb(185, 185, 185)).not_bold(),
+                            ]
+                        })
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<Span<'_>>>(),
+                ))
+                .alignment(Alignment::Center)
+                .position(block::Position::Bottom),
+            )
+            .border_set(border::DOUBLE)
+            .border_style(Style::default().fg(Color::Gray).bg(Color::Red))
+            .style(Style::default().bg(Color::Red));
+        let warn_text = Paragraph::new(Line::from(
+            match app.progress.get().map(|report| report.operation) {
+                Some(Operation::Restore) => "\nA restore is still running!",
+                Some(Operation::Verify) => "\nA verify is still running!",
+                _ => "\nA backup is still running!",
+            }
+            .bold()
+            .style(Style::default().fg(Color::White)),
+        ))
+        .centered()
+        .block(warning);
+
         frame.render_widget(warn_text, center);
     } else if action == Action::ConfirmNonExistent {
         let center = centered_rect(33, 33, vert_chunks[0]);
@@ -394,10 +905,20 @@ pub fn ui(
     ];
     let next_backup_text = vec![
         Span::styled("Next backup: ", Style::default().fg(Color::White).bold()),
-        Span::styled(
-            app.next_backup.format("%H:%M:%S").to_string(),
-            Style::default().fg(Color::LightCyan),
-        ),
+        match (app.configuration.pause, app.next_backup) {
+            (Some(Pause::Indefinitely), _) => {
+                Span::styled("Paused", Style::default().fg(Color::LightYellow).bold())
+            }
+            (Some(Pause::Until(until)), _) if until > Local::now() => Span::styled(
+                format!("Paused until {}", until.format("%Y-%m-%d %H:%M:%S")),
+                Style::default().fg(Color::LightYellow).bold(),
+            ),
+            (_, Some(next)) => Span::styled(
+                next.format("%Y-%m-%d %H:%M:%S").to_string(),
+                Style::default().fg(Color::LightCyan),
+            ),
+            (_, None) => Span::styled("Waiting for changes", Style::default().fg(Color::LightCyan)),
+        },
     ];
 
     let last_backup_block = Block::new().borders(Borders::TOP | Borders::LEFT | Borders::BOTTOM);
@@ -428,8 +949,18 @@ pub struct UIState {
     pub targets: ListState,
     pub target_change: ListState,
     pub path: ListState,
+    pub history: ListState,
+    pub history_filter: Option<HistoryKind>,
+    /// The log as last read, with the revision it was read at.
+    pub history_entries: Option<(usize, Vec<HistoryEntry>)>,
     pub cursor: usize,
     pub num_buf: Vec<String>,
+    pub text_buf: String,
+    pub text_error: Option<String>,
+    /// A new passphrase entered once, waiting to be typed again.
+    pub passphrase_first: Option<String>,
+    /// Set once the user chose to quit as soon as the running copy finishes.
+    pub quit_when_idle: bool,
 }
 
 impl UIState {
@@ -439,8 +970,15 @@ impl UIState {
             targets: ListState::default(),
             target_change: ListState::default(),
             path: ListState::default(),
+            history: ListState::default(),
+            history_filter: None,
+            history_entries: None,
             cursor: 0,
             num_buf: Vec::with_capacity(7),
+            text_buf: String::new(),
+            text_error: None,
+            passphrase_first: None,
+            quit_when_idle: false,
         }
     }
 }
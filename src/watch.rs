@@ -0,0 +1,71 @@
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+    thread::Thread,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Listens for writes under the backup targets so watch mode can back up once they settle.
+///
+/// Targets that don't exist when the watcher is created are not watched; the worker rebuilds it
+/// whenever the target list changes.
+pub struct TargetWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<()>,
+}
+
+impl TargetWatcher {
+    /// Starts watching `targets`, unparking `wake` on every change.
+    pub fn new(targets: &[PathBuf], wake: Thread) -> notify::Result<TargetWatcher> {
+        let (sender, events) = channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) {
+                        let _ = sender.send(());
+                        wake.unpark();
+                    }
+                }
+            })?;
+        for target in targets.iter().filter(|target| target.exists()) {
+            let mode = match target.is_dir() {
+                true => RecursiveMode::Recursive,
+                false => RecursiveMode::NonRecursive,
+            };
+            watcher.watch(target, mode)?;
+        }
+        Ok(TargetWatcher {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Whether anything changed since the last call.
+    pub fn changed(&self) -> bool {
+        self.events.try_iter().count() > 0
+    }
+}
+
+#[test]
+pub fn test_target_watcher() -> std::io::Result<()> {
+    use std::time::{Duration, Instant};
+
+    let root = std::env::temp_dir().join("crucible-test-watch");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("saves").join("world"))?;
+    let targets = [root.join("saves"), root.join("missing")];
+    let watcher = TargetWatcher::new(&targets, std::thread::current()).unwrap();
+    assert!(!watcher.changed());
+
+    std::fs::write(root.join("saves").join("world").join("level.dat"), b"level")?;
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !watcher.changed() {
+        assert!(Instant::now() < deadline, "no change reported");
+        std::thread::park_timeout(Duration::from_millis(100));
+    }
+    std::fs::remove_dir_all(&root)
+}
@@ -0,0 +1,315 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::prelude::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::app::{bytes_to_readable, BackupReport, CopyStats};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Discord-compatible body used when a webhook has no template of its own.
+pub const DEFAULT_TEMPLATE: &str = r#"{"content": "**Crucible** {{event}}: {{summary}}"}"#;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyOn {
+    Failure,
+    Success,
+    Prune,
+    Restore,
+}
+
+impl NotifyOn {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotifyOn::Failure => "failure",
+            NotifyOn::Success => "success",
+            NotifyOn::Prune => "prune",
+            NotifyOn::Restore => "restore",
+        }
+    }
+}
+
+fn default_on() -> Vec<NotifyOn> {
+    vec![NotifyOn::Failure, NotifyOn::Restore]
+}
+
+/// An HTTP endpoint that gets a JSON POST for the events it subscribes to.
+///
+/// `template` is the JSON body with `{{event}}`, `{{summary}}`, `{{snapshot}}`, `{{error}}`,
+/// `{{files}}`, `{{size}}` and `{{time}}` placeholders, filled in with JSON-escaped values.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default = "default_on")]
+    pub on: Vec<NotifyOn>,
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("webhook template for '{url}' is not valid JSON: {source}")]
+    Template {
+        url: String,
+        source: serde_json::Error,
+    },
+    #[error("webhook '{url}' failed: {source}")]
+    Request {
+        url: String,
+        source: Box<ureq::Error>,
+    },
+}
+
+pub struct Notification {
+    pub event: NotifyOn,
+    pub at: DateTime<Local>,
+    pub summary: String,
+    pub snapshot: Option<PathBuf>,
+    pub error: Option<String>,
+    pub stats: CopyStats,
+}
+
+impl Notification {
+    pub fn success(at: DateTime<Local>, snapshot: PathBuf, report: &BackupReport) -> Self {
+        Notification {
+            event: NotifyOn::Success,
+            at,
+            summary: report.summary(),
+            snapshot: Some(snapshot),
+            error: None,
+            stats: report.stats,
+        }
+    }
+
+    pub fn failure(at: DateTime<Local>, error: String) -> Self {
+        Notification {
+            event: NotifyOn::Failure,
+            at,
+            summary: error.clone(),
+            snapshot: None,
+            error: Some(error),
+            stats: CopyStats::default(),
+        }
+    }
+
+    pub fn prune(at: DateTime<Local>, pruned: &[PathBuf]) -> Self {
+        Notification {
+            event: NotifyOn::Prune,
+            at,
+            summary: format!(
+                "removed {} old backup(s): {}",
+                pruned.len(),
+                pruned
+                    .iter()
+                    .filter_map(|p| p.file_name())
+                    .map(|name| name.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            snapshot: None,
+            error: None,
+            stats: CopyStats::default(),
+        }
+    }
+
+    pub fn restore(at: DateTime<Local>, snapshot: PathBuf, stats: CopyStats) -> Self {
+        Notification {
+            event: NotifyOn::Restore,
+            at,
+            summary: format!(
+                "restored {} ({} files, {})",
+                snapshot.file_name().unwrap_or_default().to_string_lossy(),
+                stats.files,
+                bytes_to_readable(stats.bytes)
+            ),
+            snapshot: Some(snapshot),
+            error: None,
+            stats,
+        }
+    }
+
+    fn variables(&self) -> [(&'static str, String); 7] {
+        [
+            ("event", self.event.label().to_string()),
+            ("summary", self.summary.clone()),
+            (
+                "snapshot",
+                self.snapshot
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("error", self.error.clone().unwrap_or_default()),
+            ("files", self.stats.files.to_string()),
+            ("size", bytes_to_readable(self.stats.bytes)),
+            ("time", self.at.format("%Y-%m-%d %H:%M:%S").to_string()),
+        ]
+    }
+}
+
+/// Fills the placeholders in `template`. Values are escaped for use inside JSON strings.
+///
+/// The template is read once from left to right, so a value that itself contains a placeholder
+/// is left as it is. Unknown placeholders are kept too.
+pub fn render(template: &str, notification: &Notification) -> String {
+    let variables = notification.variables();
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let placeholder = after.find("}}").and_then(|end| {
+            let (_, value) = variables.iter().find(|(name, _)| *name == &after[..end])?;
+            Some((value, end))
+        });
+        match placeholder {
+            Some((value, end)) => {
+                let escaped = serde_json::to_string(value).unwrap_or_default();
+                body.push_str(&escaped[1..escaped.len() - 1]);
+                rest = &after[end + 2..];
+            }
+            None => {
+                body.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+pub fn send(webhook: &Webhook, notification: &Notification) -> Result<(), WebhookError> {
+    let body = render(
+        webhook.template.as_deref().unwrap_or(DEFAULT_TEMPLATE),
+        notification,
+    );
+    serde_json::from_str::<serde_json::Value>(&body).map_err(|source| WebhookError::Template {
+        url: webhook.url.clone(),
+        source,
+    })?;
+    ureq::AgentBuilder::new()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .post(&webhook.url)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map_err(|source| WebhookError::Request {
+            url: webhook.url.clone(),
+            source: Box::new(source),
+        })?;
+    Ok(())
+}
+
+/// Sends `notification` to every webhook subscribed to its event and returns the ones that failed.
+pub fn notify_all(webhooks: &[Webhook], notification: &Notification) -> Vec<WebhookError> {
+    webhooks
+        .iter()
+        .filter(|webhook| webhook.on.contains(&notification.event))
+        .filter_map(|webhook| send(webhook, notification).err())
+        .collect()
+}
+
+/// Starts an HTTP stand-in that answers one request per entry in `statuses` and returns the bodies it got.
+#[cfg(test)]
+pub fn mock_webhook_server(statuses: Vec<u16>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut bodies = Vec::new();
+        for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+#[test]
+pub fn test_webhooks() {
+    let at = Local::now();
+    let failure = Notification::failure(at, String::from("disk \"D:\" is full"));
+    assert_eq!(
+        render(DEFAULT_TEMPLATE, &failure),
+        r#"{"content": "**Crucible** failure: disk \"D:\" is full"}"#
+    );
+    // Placeholders inside a value aren't filled in again.
+    let tricky = Notification::failure(at, String::from("bad {{time}} in {{nope}}"));
+    assert_eq!(
+        render("{{error}} at {{time}}, {{summary}} {{unknown}}", &tricky),
+        format!(
+            "bad {{{{time}}}} in {{{{nope}}}} at {}, bad {{{{time}}}} in {{{{nope}}}} {{{{unknown}}}}",
+            at.format("%Y-%m-%d %H:%M:%S")
+        )
+    );
+
+    let (url, server) = mock_webhook_server(vec![204, 500]);
+    let webhooks = [
+        Webhook {
+            url: url.clone(),
+            on: vec![NotifyOn::Failure],
+            template: Some(String::from(r#"{"text": "{{error}} at {{time}}"}"#)),
+        },
+        Webhook {
+            url: url.clone(),
+            on: vec![NotifyOn::Restore],
+            template: None,
+        },
+    ];
+    assert!(notify_all(&webhooks, &failure).is_empty());
+    let errors = notify_all(
+        &webhooks,
+        &Notification::restore(
+            at,
+            PathBuf::from("2024-06-01 12-00-00"),
+            CopyStats::default(),
+        ),
+    );
+    assert!(matches!(errors[..], [WebhookError::Request { .. }]));
+
+    let bodies = server.join().unwrap();
+    let first: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+    assert_eq!(
+        first["text"],
+        format!("disk \"D:\" is full at {}", at.format("%Y-%m-%d %H:%M:%S"))
+    );
+    assert!(bodies[1].contains("restored 2024-06-01 12-00-00"));
+
+    let broken = Webhook {
+        url,
+        on: vec![NotifyOn::Failure],
+        template: Some(String::from("{\"text\": {{error}}}")),
+    };
+    assert!(matches!(
+        send(&broken, &failure),
+        Err(WebhookError::Template { .. })
+    ));
+}